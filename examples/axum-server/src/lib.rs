@@ -1,15 +1,23 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
 use axum::{
     body::Body,
-    extract::State,
-    http::Request,
+    http::{Request, Response},
     response::IntoResponse,
     routing::{get, post},
     Router,
 };
 use wasi::http::types::{IncomingRequest, ResponseOutparam};
 use wasi_hyperium::{
-    hyperium1::{handle_service_call, send_outbound_request},
+    current::{send_outbound_request, CurrentRegistry},
+    hyperium1::handle_service_call,
     poll::Poller,
+    IncomingHttpBody,
 };
 
 struct Guest;
@@ -19,11 +27,14 @@ wasi::http::proxy::export!(Guest);
 impl wasi::exports::http::incoming_handler::Guest for Guest {
     fn handle(request: IncomingRequest, response_out: ResponseOutparam) {
         let poller = Poller::default();
+        // axum boxes response bodies, which stops them from being spliced,
+        // so the proxy is served without it
+        if request.path_with_query().as_deref() == Some("/proxy") {
+            return handle_service_call(ProxyExampleCom, request, response_out, poller).unwrap();
+        }
         let router = Router::new()
             .route("/", get("Hello, WASI"))
-            .route("/echo", post(echo))
-            .route("/proxy", get(proxy_example_com))
-            .with_state(poller.clone());
+            .route("/echo", post(echo));
         handle_service_call(router, request, response_out, poller).unwrap()
     }
 }
@@ -33,11 +44,25 @@ async fn echo(body: Body) -> impl IntoResponse {
     body
 }
 
-#[axum::debug_handler]
-async fn proxy_example_com(State(poller): State<Poller>) -> impl IntoResponse {
-    let req = Request::get("https://example.com")
-        .body(Body::empty())
-        .unwrap();
-    let resp = send_outbound_request(req, poller).await.unwrap();
-    Body::new(resp.into_body())
+/// Returns the response from example.com as-is, so that its body is spliced
+/// into the outgoing response on the host.
+struct ProxyExampleCom;
+
+impl<RequestBody> tower_service::Service<Request<RequestBody>> for ProxyExampleCom {
+    type Response = Response<IncomingHttpBody<CurrentRegistry>>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Infallible>>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: Request<RequestBody>) -> Self::Future {
+        Box::pin(async {
+            let req = Request::get("https://example.com")
+                .body(Body::empty())
+                .unwrap();
+            Ok(send_outbound_request(req).await.unwrap())
+        })
+    }
 }
@@ -0,0 +1,197 @@
+use std::{
+    cell::Cell,
+    task::{Context, Poll},
+};
+
+thread_local! {
+    static BUDGET: Cell<Option<Budget>> = const { Cell::new(None) };
+    static YIELDED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The number of iterations in the default budget.
+pub const DEFAULT_ITERATIONS: usize = 128;
+/// The number of bytes in the default budget.
+pub const DEFAULT_BYTES: usize = 1024 * 1024;
+
+/// How much work a future may do in a single poll before yielding.
+///
+/// [`block_on`](crate::poll::PollableRegistry::block_on) and
+/// [`Executor`](crate::task::Executor) give each poll a budget, which the
+/// copiers and body readers in this crate consume so that a future that never
+/// waits on a pollable can't starve anything polled alongside it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Budget {
+    iterations: usize,
+    bytes: usize,
+}
+
+impl Budget {
+    pub const fn new(iterations: usize, bytes: usize) -> Self {
+        Self { iterations, bytes }
+    }
+
+    /// A budget that never runs out.
+    pub const fn unconstrained() -> Self {
+        Self::new(usize::MAX, usize::MAX)
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.iterations == 0 || self.bytes == 0
+    }
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::new(DEFAULT_ITERATIONS, DEFAULT_BYTES)
+    }
+}
+
+/// Runs `f` with the given budget, restoring the previous budget afterwards.
+pub fn with_budget<R>(budget: Budget, f: impl FnOnce() -> R) -> R {
+    run_budgeted(budget, f).0
+}
+
+/// Like [`with_budget`], also returning whether anything yielded because the
+/// budget ran out.
+pub(crate) fn run_budgeted<R>(budget: Budget, f: impl FnOnce() -> R) -> (R, bool) {
+    struct Reset {
+        budget: Option<Budget>,
+        yielded: bool,
+    }
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            BUDGET.set(self.budget);
+            YIELDED.set(self.yielded);
+        }
+    }
+
+    let _reset = Reset {
+        budget: BUDGET.replace(Some(budget)),
+        yielded: YIELDED.replace(false),
+    };
+    let ret = f();
+    (ret, YIELDED.get())
+}
+
+/// Consumes one iteration of the current budget, or wakes the task and
+/// returns Pending if the budget has run out. Always Ready outside of a
+/// budget.
+pub fn poll_proceed(cx: &mut Context) -> Poll<()> {
+    let Some(mut budget) = BUDGET.get() else {
+        return Poll::Ready(());
+    };
+    if budget.is_exhausted() {
+        YIELDED.set(true);
+        cx.waker().wake_by_ref();
+        return Poll::Pending;
+    }
+    budget.iterations -= 1;
+    BUDGET.set(Some(budget));
+    Poll::Ready(())
+}
+
+/// Records that the current poll is yielding so that other work can run, as
+/// if its budget had run out.
+pub(crate) fn note_yield() {
+    YIELDED.set(true);
+}
+
+/// Consumes `bytes` from the current budget.
+pub fn consume_bytes(bytes: usize) {
+    if let Some(mut budget) = BUDGET.get() {
+        budget.bytes = budget.bytes.saturating_sub(bytes);
+        BUDGET.set(Some(budget));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        task::{Wake, Waker},
+    };
+
+    use super::*;
+
+    #[test]
+    fn unbudgeted_polls_always_proceed() {
+        let mut cx = Context::from_waker(Waker::noop());
+        for _ in 0..DEFAULT_ITERATIONS * 2 {
+            assert!(poll_proceed(&mut cx).is_ready());
+        }
+        consume_bytes(usize::MAX);
+        assert!(poll_proceed(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn iterations_run_out() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let ((), yielded) = run_budgeted(Budget::new(2, usize::MAX), || {
+            assert!(poll_proceed(&mut cx).is_ready());
+            assert!(poll_proceed(&mut cx).is_ready());
+            assert!(poll_proceed(&mut cx).is_pending());
+        });
+        assert!(yielded);
+    }
+
+    #[test]
+    fn bytes_run_out() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let ((), yielded) = run_budgeted(Budget::new(usize::MAX, 100), || {
+            consume_bytes(60);
+            assert!(poll_proceed(&mut cx).is_ready());
+            consume_bytes(60);
+            assert!(poll_proceed(&mut cx).is_pending());
+        });
+        assert!(yielded);
+    }
+
+    #[test]
+    fn exhaustion_wakes_the_task() {
+        struct Flag(AtomicBool);
+
+        impl Wake for Flag {
+            fn wake(self: Arc<Self>) {
+                self.0.store(true, Ordering::Relaxed);
+            }
+        }
+
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        let waker = Waker::from(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+        with_budget(Budget::new(0, 0), || {
+            assert!(poll_proceed(&mut cx).is_pending());
+        });
+        assert!(flag.0.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn budgets_nest() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let ((), outer_yielded) = run_budgeted(Budget::new(1, usize::MAX), || {
+            let ((), inner_yielded) = run_budgeted(Budget::unconstrained(), || {
+                for _ in 0..10 {
+                    assert!(poll_proceed(&mut cx).is_ready());
+                }
+            });
+            assert!(!inner_yielded);
+            // The outer budget is restored untouched
+            assert!(poll_proceed(&mut cx).is_ready());
+            assert!(poll_proceed(&mut cx).is_pending());
+        });
+        assert!(outer_yielded);
+        assert!(poll_proceed(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn note_yield_counts_as_yielding() {
+        let ((), yielded) = run_budgeted(Budget::default(), note_yield);
+        assert!(yielded);
+        let ((), yielded) = run_budgeted(Budget::default(), || ());
+        assert!(!yielded);
+    }
+}
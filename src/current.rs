@@ -0,0 +1,279 @@
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    future::Future,
+    rc::Rc,
+    task::Context,
+    time::Duration,
+};
+
+use slab::Slab;
+use wasi::{clocks::monotonic_clock::Instant, io::poll::Pollable};
+
+use crate::{
+    poll::{PollableKind, PollableRegistry},
+    time::{self, Sleep, Timeout},
+    Error,
+};
+
+thread_local! {
+    // Registries that have been entered on this thread, innermost last
+    static CURRENT: RefCell<Vec<Rc<dyn DynRegistry>>> = const { RefCell::new(Vec::new()) };
+    // Entries are None while being re-armed, so the registry isn't called
+    // with REGISTRATIONS borrowed
+    static REGISTRATIONS: RefCell<Slab<Option<Box<dyn Any>>>> = const { RefCell::new(Slab::new()) };
+}
+
+/// An object-safe subset of PollableRegistry.
+trait DynRegistry {
+    fn dyn_register(
+        &self,
+        cx: &mut Context,
+        pollable: Pollable,
+        kind: PollableKind,
+    ) -> Box<dyn Any>;
+    fn dyn_rearm(&self, cx: &mut Context, registered: &mut dyn Any) -> bool;
+    fn dyn_poll(&self) -> bool;
+    fn dyn_try_poll(&self) -> bool;
+    fn dyn_record_blocking_call(&self, kind: PollableKind, duration: Duration);
+    fn dyn_id(&self) -> Option<usize>;
+    fn dyn_name(&self) -> String;
+    fn dyn_recent_registrations(&self) -> Vec<PollableKind>;
+}
+
+impl<Registry: PollableRegistry> DynRegistry for Registry {
+    fn dyn_register(
+        &self,
+        cx: &mut Context,
+        pollable: Pollable,
+        kind: PollableKind,
+    ) -> Box<dyn Any> {
+        Box::new(self.register_pollable(cx, pollable, kind))
+    }
+
+    fn dyn_rearm(&self, cx: &mut Context, registered: &mut dyn Any) -> bool {
+        // The current registry may have changed since registering
+        registered
+            .downcast_mut()
+            .is_some_and(|registered| self.rearm(cx, registered))
+    }
+
+    fn dyn_poll(&self) -> bool {
+        self.poll()
+    }
+
+    fn dyn_try_poll(&self) -> bool {
+        self.try_poll()
+    }
+
+    fn dyn_record_blocking_call(&self, kind: PollableKind, duration: Duration) {
+        self.record_blocking_call(kind, duration)
+    }
+
+    fn dyn_id(&self) -> Option<usize> {
+        self.id()
+    }
+
+    fn dyn_name(&self) -> String {
+        self.name()
+    }
+
+    fn dyn_recent_registrations(&self) -> Vec<PollableKind> {
+        self.recent_registrations()
+    }
+}
+
+/// Installs `registry` as the current registry until the returned guard is
+/// dropped. [`PollableRegistry::block_on`] does this automatically; this is
+/// only needed when a registry is driven some other way, such as by wstd.
+pub fn enter<Registry: PollableRegistry>(registry: &Registry) -> EnterGuard {
+    // CurrentRegistry delegates to the current registry; it can't be it
+    if TypeId::of::<Registry>() == TypeId::of::<CurrentRegistry>() {
+        return EnterGuard { entered: false };
+    }
+    let registry: Rc<dyn DynRegistry> = Rc::new(registry.clone());
+    CURRENT.with_borrow_mut(|current| current.push(registry));
+    EnterGuard { entered: true }
+}
+
+/// Restores the previously current registry when dropped.
+pub struct EnterGuard {
+    entered: bool,
+}
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        if self.entered {
+            let registry = CURRENT.with_borrow_mut(|current| current.pop());
+            drop(registry);
+        }
+    }
+}
+
+fn current() -> Option<Rc<dyn DynRegistry>> {
+    CURRENT.with_borrow(|current| current.last().cloned())
+}
+
+/// Returns true if a registry with the given id has been entered on this
+/// thread and not yet exited.
+pub(crate) fn is_entered(id: usize) -> bool {
+    CURRENT.with_borrow(|current| current.iter().any(|registry| registry.dyn_id() == Some(id)))
+}
+
+/// A handle to whichever registry is currently running
+/// [`PollableRegistry::block_on`] on this thread. It can be passed anywhere a
+/// registry is expected, so handlers don't need to be given one explicitly.
+///
+/// The helpers in this module check for a running registry up front. If a
+/// pollable is registered when no registry is running, nothing can wait on
+/// it, so the task is woken straight away to poll its resource again.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CurrentRegistry;
+
+impl CurrentRegistry {
+    /// Returns a handle to the current registry, or an error if no registry
+    /// is running.
+    pub fn try_current() -> Result<Self, Error> {
+        match current() {
+            Some(_) => Ok(Self),
+            None => Err(Error::NoCurrentRegistry),
+        }
+    }
+}
+
+impl PollableRegistry for CurrentRegistry {
+    type RegisteredPollable = CurrentRegistration;
+
+    fn register_pollable(
+        &self,
+        cx: &mut Context,
+        pollable: Pollable,
+        kind: PollableKind,
+    ) -> Self::RegisteredPollable {
+        let Some(registry) = current() else {
+            cx.waker().wake_by_ref();
+            return CurrentRegistration { key: None };
+        };
+        let registration = registry.dyn_register(cx, pollable, kind);
+        let key =
+            REGISTRATIONS.with_borrow_mut(|registrations| registrations.insert(Some(registration)));
+        CurrentRegistration { key: Some(key) }
+    }
+
+    fn rearm(&self, cx: &mut Context, registered: &mut Self::RegisteredPollable) -> bool {
+        let (Some(registry), Some(key)) = (current(), registered.key) else {
+            return false;
+        };
+        let take = |registrations: &mut Slab<Option<Box<dyn Any>>>| {
+            registrations.get_mut(key).and_then(Option::take)
+        };
+        let Some(mut registration) = REGISTRATIONS.with_borrow_mut(take) else {
+            return false;
+        };
+        let rearmed = registry.dyn_rearm(cx, registration.as_mut());
+        REGISTRATIONS.with_borrow_mut(|registrations| {
+            registrations[key] = Some(registration);
+        });
+        rearmed
+    }
+
+    fn poll(&self) -> bool {
+        current().is_some_and(|registry| registry.dyn_poll())
+    }
+
+    fn try_poll(&self) -> bool {
+        current().is_some_and(|registry| registry.dyn_try_poll())
+    }
+
+    fn record_blocking_call(&self, kind: PollableKind, duration: Duration) {
+        if let Some(registry) = current() {
+            registry.dyn_record_blocking_call(kind, duration);
+        }
+    }
+
+    fn id(&self) -> Option<usize> {
+        current().and_then(|registry| registry.dyn_id())
+    }
+
+    fn name(&self) -> String {
+        match current() {
+            Some(registry) => format!("CurrentRegistry({})", registry.dyn_name()),
+            None => "CurrentRegistry".into(),
+        }
+    }
+
+    fn recent_registrations(&self) -> Vec<PollableKind> {
+        current()
+            .map(|registry| registry.dyn_recent_registrations())
+            .unwrap_or_default()
+    }
+}
+
+/// A pollable registered with a [`CurrentRegistry`].
+pub struct CurrentRegistration {
+    // Registrations are kept in a thread local so that this stays Send. None
+    // if no registry was running.
+    key: Option<usize>,
+}
+
+impl Drop for CurrentRegistration {
+    fn drop(&mut self) {
+        let Some(key) = self.key else {
+            return;
+        };
+        let registration =
+            REGISTRATIONS.with_borrow_mut(|registrations| registrations.try_remove(key));
+        // Dropped outside the borrow as this may call back into the registry
+        drop(registration);
+    }
+}
+
+/// Like [`time::sleep`], using the current registry. Returns
+/// Err(NoCurrentRegistry) if no registry is running.
+pub fn sleep(duration: Duration) -> Result<Sleep<CurrentRegistry>, Error> {
+    Ok(time::sleep(duration, CurrentRegistry::try_current()?))
+}
+
+/// Like [`time::sleep_until`], using the current registry. Returns
+/// Err(NoCurrentRegistry) if no registry is running.
+pub fn sleep_until(deadline: Instant) -> Result<Sleep<CurrentRegistry>, Error> {
+    Ok(time::sleep_until(deadline, CurrentRegistry::try_current()?))
+}
+
+/// Like [`time::timeout`], using the current registry. Returns
+/// Err(NoCurrentRegistry) if no registry is running.
+pub fn timeout<F: Future>(
+    duration: Duration,
+    fut: F,
+) -> Result<Timeout<F, CurrentRegistry>, Error> {
+    Ok(time::timeout(
+        duration,
+        fut,
+        CurrentRegistry::try_current()?,
+    ))
+}
+
+/// Like [`hyperium1::send_outbound_request`](crate::hyperium1::send_outbound_request),
+/// using the current registry.
+#[cfg(feature = "hyperium1")]
+pub async fn send_outbound_request<HttpBody>(
+    request: http1::Request<HttpBody>,
+) -> Result<http1::Response<crate::IncomingHttpBody<CurrentRegistry>>, Error>
+where
+    HttpBody: http_body1::Body + Unpin,
+    HttpBody::Data: Unpin,
+    anyhow::Error: From<HttpBody::Error>,
+{
+    let registry = CurrentRegistry::try_current()?;
+    crate::hyperium1::send_outbound_request(request, registry).await
+}
+
+/// Like [`hyperium1::forward_outbound_request`](crate::hyperium1::forward_outbound_request),
+/// using the current registry.
+#[cfg(feature = "hyperium1")]
+pub async fn forward_outbound_request(
+    request: http1::Request<crate::IncomingHttpBody<CurrentRegistry>>,
+) -> Result<http1::Response<crate::IncomingHttpBody<CurrentRegistry>>, Error> {
+    let registry = CurrentRegistry::try_current()?;
+    crate::hyperium1::forward_outbound_request(request, registry).await
+}
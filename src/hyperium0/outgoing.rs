@@ -1,14 +1,12 @@
 use std::{
     pin::Pin,
-    task::{Context, Poll},
+    task::{ready, Context, Poll},
 };
 
-use bytes::Buf;
-
 use crate::{
-    outgoing::{Copied, OutgoingBodyCopier},
+    outgoing::{Copied, OutgoingBodyCopier, WriteGather},
     poll::PollableRegistry,
-    wasi::{OutgoingBody, OutgoingResponse},
+    wasi::{FieldEntries, OutgoingBody, OutgoingResponse},
     Error,
 };
 
@@ -27,8 +25,10 @@ where
 pub struct Hyperium0OutgoingBodyCopier<HttpBody: http_body0::Body, Registry: PollableRegistry> {
     src: HttpBody,
     dest: Option<OutgoingBody<Registry>>,
-    buf: Option<HttpBody::Data>,
+    pending: WriteGather<HttpBody::Data>,
     trailers_pending: bool,
+    // Some once src has ended, with its trailers if it had any
+    end: Option<Option<FieldEntries>>,
 }
 
 impl<HttpBody, Registry> Hyperium0OutgoingBodyCopier<HttpBody, Registry>
@@ -40,10 +40,19 @@ where
         Ok(Self {
             src,
             dest: Some(dest),
-            buf: None,
+            pending: WriteGather::new(),
             trailers_pending: false,
+            end: None,
         })
     }
+
+    /// Holds body data back until at least `threshold` bytes are ready to be
+    /// written or the body has ended. See
+    /// [`Hyperium1OutgoingBodyCopier::with_coalesce_threshold`](crate::hyperium1::Hyperium1OutgoingBodyCopier::with_coalesce_threshold).
+    pub fn with_coalesce_threshold(mut self, threshold: usize) -> Self {
+        self.pending.set_coalesce_threshold(threshold);
+        self
+    }
 }
 
 impl<HttpBody, Registry> OutgoingBodyCopier for Hyperium0OutgoingBodyCopier<HttpBody, Registry>
@@ -53,57 +62,48 @@ where
     Registry: PollableRegistry,
 {
     fn poll_copy(&mut self, cx: &mut Context) -> Poll<Option<Result<Copied, Error>>> {
-        if self.dest.is_none() {
+        let Some(dest) = &mut self.dest else {
             return Poll::Ready(None);
-        }
+        };
+        let stream = dest.stream();
 
-        if self.buf.is_none() && !self.trailers_pending {
-            // Fill buffer
-            match Pin::new(&mut self.src)
-                .poll_data(cx)
-                .map_err(|err| Error::BodyError(err.into()))?
-            {
-                Poll::Ready(Some(frame)) => {
-                    self.buf = Some(frame);
-                }
-                Poll::Ready(None) => {
-                    // End of body; poll for trailers next
-                    self.trailers_pending = true;
-                }
-                Poll::Pending => return Poll::Pending,
+        // Gather data while there is room to write it
+        while self.end.is_none() && self.pending.wants_more(cx, stream)? {
+            let polled = if !self.trailers_pending {
+                Pin::new(&mut self.src)
+                    .poll_data(cx)
+                    .map_err(|err| Error::BodyError(err.into()))?
+                    .map(|data| match data {
+                        Some(data) => self.pending.push(data),
+                        // End of body; poll for trailers next
+                        None => self.trailers_pending = true,
+                    })
+            } else {
+                Pin::new(&mut self.src)
+                    .poll_trailers(cx)
+                    .map_err(|err| Error::BodyError(err.into()))?
+                    .map(|trailers| self.end = Some(trailers.map(Into::into)))
+            };
+            match polled {
+                Poll::Ready(()) => (),
+                Poll::Pending if self.pending.holding() => return Poll::Pending,
+                Poll::Pending => break,
             }
         }
 
-        if self.trailers_pending {
-            return match Pin::new(&mut self.src)
-                .poll_trailers(cx)
-                .map_err(|err| Error::BodyError(err.into()))?
-            {
-                Poll::Ready(Some(trailers)) => {
-                    self.dest.take().unwrap().finish(Some(trailers.into()))?;
-                    Poll::Ready(Some(Ok(Copied::Trailers)))
-                }
-                Poll::Ready(None) => {
-                    self.dest.take().unwrap().finish(None)?;
-                    Poll::Ready(None)
-                }
-                Poll::Pending => Poll::Pending,
+        if self.pending.is_empty() {
+            // End of body; finish outgoing-body
+            let trailers = self.end.take().expect("gathered nothing before body end");
+            let copied_trailers = trailers.is_some();
+            self.dest.take().unwrap().finish(trailers)?;
+            return if copied_trailers {
+                Poll::Ready(Some(Ok(Copied::Trailers)))
+            } else {
+                Poll::Ready(None)
             };
         }
 
-        // Write buffer
-        let stream = self.dest.as_mut().unwrap().stream();
-        match stream.poll_check_write(cx)? {
-            Poll::Ready(permit) => {
-                let buf = self.buf.as_mut().unwrap();
-                let len = permit.write(buf.chunk())?;
-                buf.advance(len);
-                if !buf.has_remaining() {
-                    self.buf = None;
-                }
-                Poll::Ready(Some(Ok(Copied::Body(len))))
-            }
-            Poll::Pending => Poll::Pending,
-        }
+        let len = ready!(self.pending.poll_write(cx, stream))?;
+        Poll::Ready(Some(Ok(Copied::Body(len))))
     }
 }
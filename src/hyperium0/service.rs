@@ -36,11 +36,13 @@ where
     }
 
     let req = incoming_request(request, poller.clone())?;
-    let resp = poller.block_on(service.call(req)).unwrap().unwrap();
+    let resp = poller
+        .block_on(service.call(req))?
+        .unwrap_or_else(|never| match never {});
 
     let outgoing = outgoing_response(&resp, poller.clone())?;
     let dest = ResponseOutparam::new(response_out).set_response(outgoing);
 
     let copier = Hyperium0OutgoingBodyCopier::new(resp.into_body(), dest)?;
-    poller.block_on(copier.copy_all()).unwrap()
+    poller.block_on(copier.copy_all())?
 }
@@ -3,10 +3,16 @@ mod outgoing;
 mod send;
 mod service;
 
-pub use incoming::{incoming_request, incoming_response};
+pub use incoming::{
+    incoming_request, incoming_request_with_read_size, incoming_response,
+    incoming_response_with_read_size,
+};
 pub use outgoing::{outgoing_request, outgoing_response, Hyperium1OutgoingBodyCopier};
-pub use send::{block_on_outbound_request, send_outbound_request};
-pub use service::handle_service_call;
+pub use send::{block_on_outbound_request, forward_outbound_request, send_outbound_request};
+pub use service::{
+    call_service, handle_service_call, handle_service_call_with_options, LeftoverBody,
+    ServiceOptions,
+};
 
 use crate::wasi::{FieldEntries, Method, Scheme};
 
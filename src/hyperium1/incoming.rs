@@ -4,7 +4,7 @@ use bytes::Bytes;
 use http_body1::Frame;
 
 use crate::{
-    incoming::{IncomingHttpBody, IncomingState},
+    incoming::{IncomingHttpBody, IncomingState, ReadSize},
     poll::PollableRegistry,
     wasi::{IncomingRequest, IncomingResponse},
     Error,
@@ -36,6 +36,20 @@ where
     Ok(builder.body(request.into_body().into())?)
 }
 
+/// Like [`incoming_request`], reading the body's data frames according to
+/// `read_size`; see [`IncomingHttpBody::with_read_size`].
+pub fn incoming_request_with_read_size<Registry>(
+    request: IncomingRequest<Registry>,
+    read_size: ReadSize,
+) -> Result<http1::Request<IncomingHttpBody<Registry>>, Error>
+where
+    Registry: PollableRegistry,
+{
+    let mut request = incoming_request(request)?;
+    request.body_mut().set_read_size(read_size);
+    Ok(request)
+}
+
 pub fn incoming_response<Registry>(
     response: IncomingResponse<Registry>,
 ) -> Result<http1::Response<IncomingHttpBody<Registry>>, Error>
@@ -49,6 +63,20 @@ where
     Ok(builder.body(response.into_body().into())?)
 }
 
+/// Like [`incoming_response`], reading the body's data frames according to
+/// `read_size`; see [`IncomingHttpBody::with_read_size`].
+pub fn incoming_response_with_read_size<Registry>(
+    response: IncomingResponse<Registry>,
+    read_size: ReadSize,
+) -> Result<http1::Response<IncomingHttpBody<Registry>>, Error>
+where
+    Registry: PollableRegistry,
+{
+    let mut response = incoming_response(response)?;
+    response.body_mut().set_read_size(read_size);
+    Ok(response)
+}
+
 impl<Registry> http_body1::Body for IncomingHttpBody<Registry>
 where
     Registry: PollableRegistry,
@@ -1,14 +1,12 @@
 use std::{
     pin::Pin,
-    task::{Context, Poll},
+    task::{ready, Context, Poll},
 };
 
-use bytes::Buf;
-
 use crate::{
-    outgoing::{Copied, OutgoingBodyCopier},
+    outgoing::{Copied, OutgoingBodyCopier, WriteGather},
     poll::PollableRegistry,
-    wasi::{OutgoingBody, OutgoingRequest, OutgoingResponse},
+    wasi::{FieldEntries, OutgoingBody, OutgoingRequest, OutgoingResponse},
     Error,
 };
 
@@ -53,7 +51,9 @@ where
 {
     src: HttpBody,
     dest: Option<OutgoingBody<Registry>>,
-    buf: Option<HttpBody::Data>,
+    pending: WriteGather<HttpBody::Data>,
+    // Some once src has ended, with its trailers if it had any
+    end: Option<Option<FieldEntries>>,
 }
 
 impl<HttpBody, Registry> Hyperium1OutgoingBodyCopier<HttpBody, Registry>
@@ -65,9 +65,20 @@ where
         Ok(Self {
             src,
             dest: Some(dest),
-            buf: None,
+            pending: WriteGather::new(),
+            end: None,
         })
     }
+
+    /// Holds body data back until at least `threshold` bytes are ready to be
+    /// written or the body has ended, so that bodies made of many small
+    /// frames take fewer writes. Data frames are always gathered while the
+    /// output stream has room for them; this also waits on the body for
+    /// more. Off (0) by default.
+    pub fn with_coalesce_threshold(mut self, threshold: usize) -> Self {
+        self.pending.set_coalesce_threshold(threshold);
+        self
+    }
 }
 
 impl<HttpBody, Registry> OutgoingBodyCopier for Hyperium1OutgoingBodyCopier<HttpBody, Registry>
@@ -77,53 +88,45 @@ where
     Registry: PollableRegistry,
 {
     fn poll_copy(&mut self, cx: &mut Context) -> Poll<Option<Result<Copied, Error>>> {
-        if self.dest.is_none() {
+        let Some(dest) = &mut self.dest else {
             return Poll::Ready(None);
-        }
+        };
+        let stream = dest.stream();
 
-        if self.buf.is_none() {
-            // Fill buffer
+        // Gather frames while there is room to write them
+        while self.end.is_none() && self.pending.wants_more(cx, stream)? {
             match Pin::new(&mut self.src)
                 .poll_frame(cx)
                 .map_err(|err| Error::BodyError(err.into()))?
             {
-                Poll::Ready(Some(frame)) => {
-                    if frame.is_data() {
-                        self.buf =
-                            Some(frame.into_data().unwrap_or_else(|_| {
-                                panic!("into_data failed when is_data = true")
-                            }));
-                    } else {
-                        // Got trailers; finish outgoing-body
-                        let trailers = frame.into_trailers().unwrap_or_else(|_| {
-                            panic!("into_trailers failed when is_data = false")
-                        });
-                        self.dest.take().unwrap().finish(Some(trailers.into()))?;
-                        return Poll::Ready(Some(Ok(Copied::Trailers)));
+                Poll::Ready(Some(frame)) => match frame.into_data() {
+                    Ok(data) => self.pending.push(data),
+                    Err(frame) => {
+                        let trailers = frame
+                            .into_trailers()
+                            .unwrap_or_else(|_| panic!("into_trailers failed when into_data did"));
+                        self.end = Some(Some(trailers.into()));
                     }
-                }
-                Poll::Ready(None) => {
-                    // End of body (no trailers); finish outgoing-body
-                    self.dest.take().unwrap().finish(None)?;
-                    return Poll::Ready(None);
-                }
-                Poll::Pending => return Poll::Pending,
+                },
+                Poll::Ready(None) => self.end = Some(None),
+                Poll::Pending if self.pending.holding() => return Poll::Pending,
+                Poll::Pending => break,
             }
         }
 
-        // Write buffer
-        let stream = self.dest.as_mut().unwrap().stream();
-        match stream.poll_check_write(cx)? {
-            Poll::Ready(permit) => {
-                let buf = self.buf.as_mut().unwrap();
-                let len = permit.write(buf.chunk())?;
-                buf.advance(len);
-                if !buf.has_remaining() {
-                    self.buf = None;
-                }
-                Poll::Ready(Some(Ok(Copied::Body(len))))
-            }
-            Poll::Pending => Poll::Pending,
+        if self.pending.is_empty() {
+            // End of body; finish outgoing-body
+            let trailers = self.end.take().expect("gathered nothing before body end");
+            let copied_trailers = trailers.is_some();
+            self.dest.take().unwrap().finish(trailers)?;
+            return if copied_trailers {
+                Poll::Ready(Some(Ok(Copied::Trailers)))
+            } else {
+                Poll::Ready(None)
+            };
         }
+
+        let len = ready!(self.pending.poll_write(cx, stream))?;
+        Poll::Ready(Some(Ok(Copied::Body(len))))
     }
 }
@@ -2,7 +2,7 @@ use futures_util::future;
 
 use crate::{
     hyperium1::{incoming_response, Hyperium1OutgoingBodyCopier},
-    outgoing::OutgoingBodyCopier,
+    outgoing::{OutgoingBodyCopier, SpliceCopier},
     poll::PollableRegistry,
     wasi::OutgoingRequest,
     Error, IncomingHttpBody,
@@ -10,6 +10,9 @@ use crate::{
 
 use super::outgoing_request;
 
+/// Sends `request` and blocks until the response head arrives. Inside a
+/// future already driven by `registry`, await [`send_outbound_request`]
+/// instead; calling this there returns a re-entrancy error.
 pub fn block_on_outbound_request<HttpBody, Registry>(
     request: http1::Request<HttpBody>,
     registry: Registry,
@@ -22,8 +25,7 @@ where
 {
     registry
         .clone()
-        .block_on(send_outbound_request(request, registry))
-        .unwrap()
+        .block_on(send_outbound_request(request, registry))?
 }
 
 pub async fn send_outbound_request<HttpBody, Registry>(
@@ -43,3 +45,20 @@ where
     let (response, _) = future::try_join(future_response, copier).await?;
     incoming_response(response)
 }
+
+/// Like [`send_outbound_request`], for a request whose body came from WASI,
+/// e.g. the incoming request being proxied. The body is spliced on the host
+/// instead of being copied through guest memory.
+pub async fn forward_outbound_request<Registry>(
+    request: http1::Request<IncomingHttpBody<Registry>>,
+    registry: Registry,
+) -> Result<http1::Response<IncomingHttpBody<Registry>>, Error>
+where
+    Registry: PollableRegistry,
+{
+    let outgoing: OutgoingRequest<_> = outgoing_request(&request, registry.clone())?;
+    let (outgoing_body, future_response) = outgoing.send(None)?.into_parts();
+    let copier = SpliceCopier::new(request.into_body(), outgoing_body).copy_all();
+    let (response, _) = future::try_join(future_response, copier).await?;
+    incoming_response(response)
+}
@@ -1,19 +1,86 @@
-use std::{convert::Infallible, task::Context};
+use std::{any::Any, convert::Infallible, future::Future, time::Duration};
 
-use wasi::http::types;
+use wasi::{clocks::monotonic_clock::Instant, http::types};
 
 use crate::{
+    current::CurrentRegistry,
     hyperium1::{incoming_request, outgoing_response},
-    outgoing::OutgoingBodyCopier,
-    poll::{noop_waker, PollableRegistry},
-    wasi::{IncomingRequest, ResponseOutparam},
-    Error, IncomingHttpBody,
+    outgoing::{OutgoingBodyCopier, SpliceCopier},
+    poll::{BlockOnError, PollableRegistry},
+    stats::PollStats,
+    time::{self, Elapsed},
+    wasi::{IncomingRequest, OutgoingResponse, ResponseOutparam},
+    Error, IncomingHttpBody, ReadSize,
 };
 
 use super::Hyperium1OutgoingBodyCopier;
 
+/// Options for [`handle_service_call_with_options`] and [`call_service`].
+#[derive(Clone, Debug, Default)]
+pub struct ServiceOptions {
+    timeout: Option<Duration>,
+    poll_stats: Option<PollStats>,
+    read_size: Option<ReadSize>,
+    coalesce_threshold: usize,
+    leftover_body: LeftoverBody,
+}
+
+/// What to do with a request body the service left unread.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LeftoverBody {
+    /// Drop it; the host may treat the request as cancelled.
+    #[default]
+    Cancel,
+    /// Once the response has been written, skip the rest of the body and
+    /// consume its trailers.
+    Drain,
+}
+
+impl ServiceOptions {
+    /// Sets a deadline for the whole service call, measured from when the
+    /// call starts. If the service has not produced a response by then, a
+    /// `504 Gateway Timeout` response is sent instead. If the response body
+    /// is still being written, or a leftover request body still being
+    /// drained, it is abandoned and an error returned.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Reports `stats` once the service call and any scoped tasks have
+    /// completed. `stats` should be observing the registry given to
+    /// [`handle_service_call_with_options`], e.g. via
+    /// [`Poller::with_observer`](crate::poll::Poller::with_observer).
+    pub fn report_poll_stats(mut self, stats: PollStats) -> Self {
+        self.poll_stats = Some(stats);
+        self
+    }
+
+    /// Sets how much of the request body is read for each data frame; see
+    /// [`IncomingHttpBody::with_read_size`].
+    pub fn request_read_size(mut self, read_size: ReadSize) -> Self {
+        self.read_size = Some(read_size);
+        self
+    }
+
+    /// Coalesces the response body's writes; see
+    /// [`Hyperium1OutgoingBodyCopier::with_coalesce_threshold`].
+    pub fn coalesce_threshold(mut self, threshold: usize) -> Self {
+        self.coalesce_threshold = threshold;
+        self
+    }
+
+    /// Sets what happens to a request body the service drops without
+    /// reading to the end. A body the service still holds on to is left
+    /// alone.
+    pub fn leftover_body(mut self, leftover_body: LeftoverBody) -> Self {
+        self.leftover_body = leftover_body;
+        self
+    }
+}
+
 pub fn handle_service_call<Service, ResponseBody, Registry>(
-    mut service: Service,
+    service: Service,
     request: types::IncomingRequest,
     response_out: types::ResponseOutparam,
     registry: Registry,
@@ -24,28 +91,182 @@ where
         Response = http1::Response<ResponseBody>,
         Error = Infallible,
     >,
-    ResponseBody: http_body1::Body + Unpin,
+    ResponseBody: http_body1::Body + Unpin + 'static,
     ResponseBody::Data: Unpin,
     anyhow::Error: From<ResponseBody::Error>,
     Registry: PollableRegistry,
 {
-    let waker = noop_waker();
-    let mut cx = Context::from_waker(&waker);
+    handle_service_call_with_options(
+        service,
+        request,
+        response_out,
+        registry,
+        ServiceOptions::default(),
+    )
+}
 
-    while service.poll_ready(&mut cx).is_pending() {
-        if !registry.poll() {
-            panic!("service never became ready");
-        }
+pub fn handle_service_call_with_options<Service, ResponseBody, Registry>(
+    service: Service,
+    request: types::IncomingRequest,
+    response_out: types::ResponseOutparam,
+    registry: Registry,
+    options: ServiceOptions,
+) -> Result<(), Error>
+where
+    Service: tower_service::Service<
+        http1::Request<IncomingHttpBody<Registry>>,
+        Response = http1::Response<ResponseBody>,
+        Error = Infallible,
+    >,
+    ResponseBody: http_body1::Body + Unpin + 'static,
+    ResponseBody::Data: Unpin,
+    anyhow::Error: From<ResponseBody::Error>,
+    Registry: PollableRegistry,
+{
+    let poll_stats = options.poll_stats.clone();
+    let call = call_service(service, request, response_out, registry.clone(), options);
+    let result = block_on_service_call(call, &registry);
+    if let Some(stats) = poll_stats {
+        stats.report();
     }
+    result
+}
+
+fn block_on_service_call<Registry>(
+    call: impl Future<Output = Result<(), Error>>,
+    registry: &Registry,
+) -> Result<(), Error>
+where
+    Registry: PollableRegistry,
+{
+    registry.block_on(call)??;
+
+    // Wait for any scoped tasks spawned by the service
+    registry.join_all()?;
+    Ok(())
+}
+
+/// Calls the service and writes its response without blocking. This is the
+/// async core of [`handle_service_call_with_options`], for use when the
+/// registry is driven by another runtime.
+///
+/// If the response body is an [`IncomingHttpBody`] using `Registry` or a
+/// [`CurrentRegistry`], e.g. the response from
+/// [`forward_outbound_request`](super::forward_outbound_request) or
+/// [`current::send_outbound_request`](crate::current::send_outbound_request)
+/// returned as-is, it is spliced on the host rather than copied through
+/// guest memory. Bodies wrapped in another type, such as axum's `Body`, are
+/// copied. Detecting this is why `ResponseBody` must be `'static`, which
+/// hyper and tower based frameworks already require of their bodies.
+pub async fn call_service<Service, ResponseBody, Registry>(
+    mut service: Service,
+    request: types::IncomingRequest,
+    response_out: types::ResponseOutparam,
+    registry: Registry,
+    options: ServiceOptions,
+) -> Result<(), Error>
+where
+    Service: tower_service::Service<
+        http1::Request<IncomingHttpBody<Registry>>,
+        Response = http1::Response<ResponseBody>,
+        Error = Infallible,
+    >,
+    ResponseBody: http_body1::Body + Unpin + 'static,
+    ResponseBody::Data: Unpin,
+    anyhow::Error: From<ResponseBody::Error>,
+    Registry: PollableRegistry,
+{
+    let deadline = options.timeout.map(time::deadline_after);
+
+    std::future::poll_fn(|cx| service.poll_ready(cx))
+        .await
+        .unwrap_or_else(|never| match never {});
 
     let incoming = IncomingRequest::new(request, registry.clone())?;
-    let req = incoming_request(incoming)?;
+    let mut req = incoming_request(incoming)?;
+    if let Some(read_size) = options.read_size {
+        req.body_mut().set_read_size(read_size);
+    }
+    let leftover = match options.leftover_body {
+        LeftoverBody::Cancel => None,
+        LeftoverBody::Drain => Some(req.body_mut().leftover()),
+    };
 
-    let resp = registry.block_on(service.call(req)).unwrap().unwrap();
+    let resp = match with_deadline(deadline, service.call(req), &registry).await {
+        Ok(resp) => resp.unwrap_or_else(|never| match never {}),
+        Err(Elapsed) => return respond_gateway_timeout(response_out, registry),
+    };
 
     let outgoing = outgoing_response(&resp, registry.clone())?;
     let dest = ResponseOutparam::new(response_out).set_response(outgoing);
 
-    let copier = Hyperium1OutgoingBodyCopier::new(resp.into_body(), dest)?;
-    registry.block_on(copier.copy_all()).unwrap()
+    let body = resp.into_body();
+    let copied = match downcast::<IncomingHttpBody<Registry>, _>(body) {
+        Ok(body) => {
+            let copier = SpliceCopier::new(body, dest);
+            with_deadline(deadline, copier.copy_all(), &registry).await
+        }
+        Err(body) => match downcast::<IncomingHttpBody<CurrentRegistry>, _>(body) {
+            Ok(body) => {
+                let copier = SpliceCopier::new(body, dest);
+                with_deadline(deadline, copier.copy_all(), &registry).await
+            }
+            Err(body) => {
+                let copier = Hyperium1OutgoingBodyCopier::new(body, dest)?
+                    .with_coalesce_threshold(options.coalesce_threshold);
+                with_deadline(deadline, copier.copy_all(), &registry).await
+            }
+        },
+    };
+    copied.map_err(|Elapsed| BlockOnError::TimedOut)??;
+
+    if let Some(body) = leftover.and_then(|leftover| leftover.take()) {
+        with_deadline(deadline, body.discard(), &registry)
+            .await
+            .map_err(|Elapsed| BlockOnError::TimedOut)??;
+    }
+    Ok(())
+}
+
+/// Returns `body` as a `T` if that is what it is, e.g. so that an
+/// IncomingHttpBody can be spliced.
+fn downcast<T, HttpBody>(body: HttpBody) -> Result<T, HttpBody>
+where
+    T: 'static,
+    HttpBody: 'static,
+{
+    let mut body = Some(body);
+    match (&mut body as &mut dyn Any).downcast_mut::<Option<T>>() {
+        Some(incoming) => Ok(incoming.take().unwrap()),
+        None => Err(body.unwrap()),
+    }
+}
+
+async fn with_deadline<T, Registry>(
+    deadline: Option<Instant>,
+    fut: impl Future<Output = T>,
+    registry: &Registry,
+) -> Result<T, Elapsed>
+where
+    Registry: PollableRegistry,
+{
+    match deadline {
+        Some(deadline) => time::timeout_at(deadline, fut, registry.clone()).await,
+        None => Ok(fut.await),
+    }
+}
+
+fn respond_gateway_timeout<Registry>(
+    response_out: types::ResponseOutparam,
+    registry: Registry,
+) -> Result<(), Error>
+where
+    Registry: PollableRegistry,
+{
+    let mut outgoing =
+        OutgoingResponse::new(types::OutgoingResponse::new(types::Fields::new()), registry)?;
+    outgoing.set_status_code(504)?;
+    ResponseOutparam::new(response_out)
+        .set_response(outgoing)
+        .finish(None)
 }
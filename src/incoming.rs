@@ -1,13 +1,15 @@
 use std::{
     future::Future,
     pin::Pin,
-    task::{Context, Poll},
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
 };
 
 use bytes::Bytes;
 use wasi::http::types;
 
 use crate::{
+    coop,
     poll::PollableRegistry,
     wasi::{FieldEntries, FutureTrailers, IncomingBody},
     Error,
@@ -18,6 +20,30 @@ where
     Registry: PollableRegistry,
 {
     pub(crate) state: IncomingState<Registry>,
+    read_size: ReadSize,
+    // The next frame's size; only changes when read_size is Adaptive
+    frame_size: usize,
+    // Receives the unread state on drop; see leftover
+    leftover: Option<LeftoverSlot<Registry>>,
+}
+
+type LeftoverSlot<Registry> = Arc<Mutex<Option<IncomingState<Registry>>>>;
+
+/// Holds whatever of an [`IncomingHttpBody`] was left unread when it was
+/// dropped. Returned by [`IncomingHttpBody::leftover`].
+#[cfg(feature = "hyperium1")]
+pub(crate) struct Leftover<Registry: PollableRegistry>(LeftoverSlot<Registry>);
+
+#[cfg(feature = "hyperium1")]
+impl<Registry> Leftover<Registry>
+where
+    Registry: PollableRegistry,
+{
+    /// Returns the rest of the body if it has been dropped unfinished.
+    pub(crate) fn take(&self) -> Option<IncomingHttpBody<Registry>> {
+        let state = self.0.lock().unwrap().take()?;
+        Some(IncomingHttpBody::from_state(state))
+    }
 }
 
 pub(crate) enum IncomingState<Registry>
@@ -31,6 +57,49 @@ where
 
 const READ_FRAME_SIZE: usize = 16 * 1024;
 
+/// How much an [`IncomingHttpBody`] reads for each data frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadSize {
+    /// Reads up to this many bytes at a time.
+    Fixed(usize),
+    /// Starts at `min` and doubles, up to `max`, each time a read fills the
+    /// whole frame. Halves again, down to `min`, when a read fills less than
+    /// half of it.
+    Adaptive { min: usize, max: usize },
+}
+
+impl ReadSize {
+    fn initial(self) -> usize {
+        match self {
+            Self::Fixed(size) => size,
+            Self::Adaptive { min, .. } => min,
+        }
+        .max(1)
+    }
+
+    /// Returns the size of the frame to read after reading `read` bytes into
+    /// a frame of `current` bytes.
+    fn next(self, current: usize, read: usize) -> usize {
+        match self {
+            Self::Fixed(_) => current,
+            Self::Adaptive { min, max } if read >= current => {
+                max.min(current.saturating_mul(2)).max(min)
+            }
+            Self::Adaptive { min, .. } if read < current / 2 => min.max(current / 2).max(1),
+            Self::Adaptive { .. } => current,
+        }
+    }
+}
+
+impl Default for ReadSize {
+    fn default() -> Self {
+        Self::Fixed(READ_FRAME_SIZE)
+    }
+}
+
+/// How much discard asks the host to skip at once.
+const SKIP_SIZE: u64 = 1024 * 1024;
+
 impl<Registry> IncomingHttpBody<Registry>
 where
     Registry: PollableRegistry,
@@ -39,19 +108,56 @@ where
         Ok(IncomingBody::new(body, registry)?.into())
     }
 
+    /// Sets how much is read for each data frame. Defaults to
+    /// `ReadSize::Fixed(16 KiB)`.
+    pub fn with_read_size(mut self, read_size: ReadSize) -> Self {
+        self.set_read_size(read_size);
+        self
+    }
+
+    pub fn set_read_size(&mut self, read_size: ReadSize) {
+        self.read_size = read_size;
+        self.frame_size = read_size.initial();
+    }
+
     pub fn poll_incoming_body(&mut self, cx: &mut Context) -> Poll<Option<Result<Bytes, Error>>> {
-        let IncomingState::Body(incoming_body) = &mut self.state else {
+        if !matches!(self.state, IncomingState::Body(_)) {
             panic!("poll_incoming_body called on non-body state")
+        }
+        self.poll_read_frame(cx).map(Result::transpose)
+    }
+
+    /// Reads the next data frame, sized according to read_size.
+    fn poll_read_frame(&mut self, cx: &mut Context) -> Poll<Result<Option<Bytes>, Error>> {
+        let data = ready!(self.poll_read_body(cx, self.frame_size))?;
+        if let Some(data) = &data {
+            self.frame_size = self.read_size.next(self.frame_size, data.len());
+        }
+        Poll::Ready(Ok(data))
+    }
+
+    /// Reads up to `len` bytes of the body, returning None once it has ended
+    /// and moving on to the trailers.
+    pub(crate) fn poll_read_body(
+        &mut self,
+        cx: &mut Context,
+        len: usize,
+    ) -> Poll<Result<Option<Bytes>, Error>> {
+        let IncomingState::Body(incoming_body) = &mut self.state else {
+            return Poll::Ready(Ok(None));
         };
 
-        match incoming_body.stream().poll_read(cx, READ_FRAME_SIZE) {
-            Poll::Ready(Ok(data)) => Poll::Ready(Some(Ok(data.into()))),
-            Poll::Ready(Err(Error::WasiStreamClosed)) => {
+        ready!(coop::poll_proceed(cx));
+        match ready!(incoming_body.stream().poll_read(cx, len)) {
+            Ok(data) => {
+                coop::consume_bytes(data.len());
+                Poll::Ready(Ok(Some(data.into())))
+            }
+            Err(Error::WasiStreamClosed) => {
                 self.state = IncomingState::Trailers(self.take_body().finish());
-                Poll::Ready(None)
+                Poll::Ready(Ok(None))
             }
-            Poll::Ready(Err(err)) => Poll::Ready(Some(Err(err))),
-            Poll::Pending => Poll::Pending,
+            Err(err) => Poll::Ready(Err(err)),
         }
     }
 
@@ -82,6 +188,79 @@ where
         }
     }
 
+    /// Blocks until up to `len` bytes of the body can be read, returning None
+    /// once it has ended.
+    pub(crate) fn blocking_read_body(&mut self, len: usize) -> Result<Option<Vec<u8>>, Error> {
+        let IncomingState::Body(incoming_body) = &mut self.state else {
+            return Ok(None);
+        };
+        match incoming_body.stream().blocking_read(len) {
+            Ok(data) => Ok(Some(data)),
+            Err(Error::WasiStreamClosed) => {
+                self.state = IncomingState::Trailers(self.take_body().finish());
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Blocks until the trailers are available, discarding any unread body.
+    pub fn blocking_finish(mut self) -> Result<Option<FieldEntries>, Error> {
+        let trailers = match std::mem::replace(&mut self.state, IncomingState::Empty) {
+            IncomingState::Empty => return Ok(None),
+            IncomingState::Body(body) => body.finish(),
+            IncomingState::Trailers(trailers) => trailers,
+        };
+        match trailers.blocking_get() {
+            // See poll_incoming_trailers
+            Err(Error::WasiErrorCode(s)) if s.contains("ConnectionTerminated") => Ok(None),
+            res => res,
+        }
+    }
+
+    /// Skips the rest of the body on the host and consumes its trailers, so
+    /// the host sees the body read to completion.
+    pub async fn discard(mut self) -> Result<(), Error> {
+        std::future::poll_fn(|cx| self.poll_discard(cx)).await
+    }
+
+    pub fn poll_discard(&mut self, cx: &mut Context) -> Poll<Result<(), Error>> {
+        while let IncomingState::Body(incoming_body) = &mut self.state {
+            ready!(coop::poll_proceed(cx));
+            match ready!(incoming_body.stream().poll_skip(cx, SKIP_SIZE)) {
+                Ok(_) => (),
+                Err(Error::WasiStreamClosed) => {
+                    self.state = IncomingState::Trailers(self.take_body().finish());
+                }
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+        ready!(self.poll_incoming_trailers(cx))?;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Arranges for the unread state to be kept when this body is dropped,
+    /// so that it can be drained afterwards.
+    #[cfg(feature = "hyperium1")]
+    pub(crate) fn leftover(&mut self) -> Leftover<Registry> {
+        let slot = self.leftover.get_or_insert_with(Default::default);
+        Leftover(slot.clone())
+    }
+
+    fn from_state(state: IncomingState<Registry>) -> Self {
+        Self {
+            state,
+            read_size: ReadSize::default(),
+            frame_size: READ_FRAME_SIZE,
+            leftover: None,
+        }
+    }
+
+    /// Returns a stream of the body's data followed by its trailers, if any.
+    pub fn frames(self) -> Frames<Registry> {
+        Frames { body: self }
+    }
+
     pub(crate) fn take_body(&mut self) -> IncomingBody<Registry> {
         match std::mem::replace(&mut self.state, IncomingState::Empty) {
             IncomingState::Body(body) => body,
@@ -95,8 +274,139 @@ where
     Registry: PollableRegistry,
 {
     fn from(body: IncomingBody<Registry>) -> Self {
-        Self {
-            state: IncomingState::Body(body),
+        Self::from_state(IncomingState::Body(body))
+    }
+}
+
+impl<Registry> Drop for IncomingHttpBody<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn drop(&mut self) {
+        if let Some(slot) = self.leftover.take() {
+            if !matches!(self.state, IncomingState::Empty) {
+                let state = std::mem::replace(&mut self.state, IncomingState::Empty);
+                *slot.lock().unwrap() = Some(state);
+            }
+        }
+    }
+}
+
+/// Yields the body's data. Trailers are skipped; see
+/// [`IncomingHttpBody::frames`].
+impl<Registry> futures_core::Stream for IncomingHttpBody<Registry>
+where
+    Registry: PollableRegistry,
+{
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_read_frame(cx).map(Result::transpose)
+    }
+}
+
+/// Yields the body's data.
+impl<Registry> futures_core::Stream for IncomingBody<Registry>
+where
+    Registry: PollableRegistry,
+{
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        ready!(coop::poll_proceed(cx));
+        match ready!(self.get_mut().stream().poll_read(cx, READ_FRAME_SIZE)) {
+            Ok(data) => {
+                coop::consume_bytes(data.len());
+                Poll::Ready(Some(Ok(data.into())))
+            }
+            Err(Error::WasiStreamClosed) => Poll::Ready(None),
+            Err(err) => Poll::Ready(Some(Err(err))),
+        }
+    }
+}
+
+/// A frame of an [`IncomingHttpBody`].
+#[derive(Debug)]
+pub enum BodyFrame {
+    Data(Bytes),
+    Trailers(FieldEntries),
+}
+
+/// A stream of a body's data followed by its trailers, if any. Returned by
+/// [`IncomingHttpBody::frames`].
+pub struct Frames<Registry: PollableRegistry> {
+    body: IncomingHttpBody<Registry>,
+}
+
+impl<Registry> futures_core::Stream for Frames<Registry>
+where
+    Registry: PollableRegistry,
+{
+    type Item = Result<BodyFrame, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let body = &mut self.get_mut().body;
+        if let IncomingState::Body(_) = body.state {
+            match ready!(body.poll_read_frame(cx)) {
+                Ok(Some(data)) => return Poll::Ready(Some(Ok(BodyFrame::Data(data)))),
+                Ok(None) => (),
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            }
         }
+        match ready!(body.poll_incoming_trailers(cx)) {
+            Ok(Some(trailers)) => Poll::Ready(Some(Ok(BodyFrame::Trailers(trailers)))),
+            Ok(None) => Poll::Ready(None),
+            Err(err) => Poll::Ready(Some(Err(err))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_read_size_never_changes() {
+        let read_size = ReadSize::Fixed(1024);
+        assert_eq!(read_size.initial(), 1024);
+        assert_eq!(read_size.next(1024, 1024), 1024);
+        assert_eq!(read_size.next(1024, 1), 1024);
+        assert_eq!(ReadSize::Fixed(0).initial(), 1);
+    }
+
+    #[test]
+    fn adaptive_read_size_grows_when_full() {
+        let read_size = ReadSize::Adaptive {
+            min: 1024,
+            max: 4096,
+        };
+        assert_eq!(read_size.initial(), 1024);
+        assert_eq!(read_size.next(1024, 1024), 2048);
+        assert_eq!(read_size.next(2048, 2048), 4096);
+        assert_eq!(read_size.next(4096, 4096), 4096);
+    }
+
+    #[test]
+    fn adaptive_read_size_shrinks_when_under_half_full() {
+        let read_size = ReadSize::Adaptive {
+            min: 1024,
+            max: 4096,
+        };
+        assert_eq!(read_size.next(4096, 2048), 4096);
+        assert_eq!(read_size.next(4096, 2047), 2048);
+        assert_eq!(read_size.next(2048, 10), 1024);
+        assert_eq!(read_size.next(1024, 10), 1024);
+    }
+
+    #[test]
+    fn adaptive_read_size_stays_in_bounds() {
+        let read_size = ReadSize::Adaptive { min: 0, max: 0 };
+        assert_eq!(read_size.initial(), 1);
+        assert_eq!(read_size.next(1, 0), 1);
+        let read_size = ReadSize::Adaptive {
+            min: 1,
+            max: usize::MAX,
+        };
+        assert_eq!(read_size.next(usize::MAX, usize::MAX), usize::MAX);
     }
 }
@@ -0,0 +1,300 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use crate::{
+    poll::PollableRegistry,
+    wasi::{IncomingBody, InputStream, OutgoingBody, OutputStream},
+    Error, IncomingHttpBody,
+};
+
+/// Blocks on the stream; see [`InputStream::blocking_read`].
+impl<Registry> io::Read for InputStream<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.blocking_read(buf.len()) {
+            Ok(data) => {
+                buf[..data.len()].copy_from_slice(&data);
+                Ok(data.len())
+            }
+            Err(Error::WasiStreamClosed) => Ok(0),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl<Registry> io::Read for IncomingBody<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream().read(buf)
+    }
+}
+
+/// Blocks on the body, ignoring any trailers; see
+/// [`IncomingHttpBody::blocking_finish`].
+impl<Registry> io::Read for IncomingHttpBody<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let Some(data) = self.blocking_read_body(buf.len())? else {
+            return Ok(0);
+        };
+        buf[..data.len()].copy_from_slice(&data);
+        Ok(data.len())
+    }
+}
+
+/// Blocks on the stream; see [`OutputStream::blocking_write`].
+impl<Registry> io::Write for OutputStream<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.blocking_write(buf)?)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(self.blocking_flush()?)
+    }
+}
+
+/// Writes go to the body's stream; call [`OutgoingBody::finish`] to end the
+/// body.
+impl<Registry> io::Write for OutgoingBody<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream().flush()
+    }
+}
+
+impl<Registry> futures_io::AsyncRead for InputStream<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        match ready!(self.get_mut().poll_read(cx, buf.len())) {
+            Ok(data) => {
+                buf[..data.len()].copy_from_slice(&data);
+                Poll::Ready(Ok(data.len()))
+            }
+            Err(Error::WasiStreamClosed) => Poll::Ready(Ok(0)),
+            Err(err) => Poll::Ready(Err(err.into())),
+        }
+    }
+}
+
+impl<Registry> futures_io::AsyncBufRead for InputStream<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.get_mut().poll_fill_buf(cx).map_err(Into::into)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().consume(amt)
+    }
+}
+
+impl<Registry> futures_io::AsyncWrite for OutputStream<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_write(cx, buf).map_err(Into::into)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush(cx).map_err(Into::into)
+    }
+
+    /// Flushes the stream; it is closed when dropped.
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl<Registry> futures_io::AsyncRead for IncomingBody<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(self.get_mut().stream()).poll_read(cx, buf)
+    }
+}
+
+impl<Registry> futures_io::AsyncBufRead for IncomingBody<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(self.get_mut().stream()).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().stream().consume(amt)
+    }
+}
+
+/// Writes go to the body's stream. Closing only flushes it; call
+/// [`OutgoingBody::finish`] to end the body.
+impl<Registry> futures_io::AsyncWrite for OutgoingBody<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(self.get_mut().stream()).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(self.get_mut().stream()).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(self.get_mut().stream()).poll_close(cx)
+    }
+}
+
+#[cfg(feature = "tokio-io")]
+mod tokio_io {
+    use std::{
+        io,
+        pin::Pin,
+        task::{ready, Context, Poll},
+    };
+
+    use tokio::io::ReadBuf;
+
+    use crate::{
+        poll::PollableRegistry,
+        wasi::{InputStream, OutgoingBody, OutputStream},
+        Error, IncomingHttpBody,
+    };
+
+    impl<Registry> tokio::io::AsyncRead for InputStream<Registry>
+    where
+        Registry: PollableRegistry,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            match ready!(self.get_mut().poll_read(cx, buf.remaining())) {
+                Ok(data) => buf.put_slice(&data),
+                Err(Error::WasiStreamClosed) => (),
+                Err(err) => return Poll::Ready(Err(err.into())),
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<Registry> tokio::io::AsyncRead for IncomingHttpBody<Registry>
+    where
+        Registry: PollableRegistry,
+    {
+        /// Reads the body, ignoring any trailers.
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            if let Some(data) = ready!(self.get_mut().poll_read_body(cx, buf.remaining()))? {
+                buf.put_slice(&data);
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<Registry> tokio::io::AsyncWrite for OutputStream<Registry>
+    where
+        Registry: PollableRegistry,
+    {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write(cx, buf).map_err(Into::into)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().poll_flush(cx).map_err(Into::into)
+        }
+
+        /// Flushes the stream; it is closed when dropped.
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().poll_flush(cx).map_err(Into::into)
+        }
+    }
+
+    /// Writes go to the body's stream. Shutting down only flushes it; call
+    /// [`OutgoingBody::finish`] to end the body.
+    impl<Registry> tokio::io::AsyncWrite for OutgoingBody<Registry>
+    where
+        Registry: PollableRegistry,
+    {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut()
+                .stream()
+                .poll_write(cx, buf)
+                .map_err(Into::into)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().stream().poll_flush(cx).map_err(Into::into)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().stream().poll_flush(cx).map_err(Into::into)
+        }
+    }
+}
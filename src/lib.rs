@@ -1,10 +1,18 @@
+pub mod coop;
+pub mod current;
 mod incoming;
+mod io;
 pub mod outgoing;
 pub mod poll;
+pub mod stats;
+pub mod task;
+pub mod time;
 pub mod wasi;
+#[cfg(feature = "wstd")]
+pub mod wstd;
 
 use ::wasi::{http::types::ErrorCode, io::streams::StreamError};
-pub use incoming::IncomingHttpBody;
+pub use incoming::{BodyFrame, Frames, IncomingHttpBody, ReadSize};
 
 #[cfg(feature = "hyperium0")]
 pub mod hyperium0;
@@ -31,6 +39,11 @@ pub enum Error {
     #[error("stream closed")]
     WasiStreamClosed,
 
+    #[error(transparent)]
+    BlockOn(#[from] poll::BlockOnError),
+    #[error("no PollableRegistry is running on this thread")]
+    NoCurrentRegistry,
+
     #[cfg(feature = "hyperium0")]
     #[error(transparent)]
     Hyperium0Error(#[from] http0::Error),
@@ -39,6 +52,12 @@ pub enum Error {
     Hyperium1Error(#[from] http1::Error),
 }
 
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        std::io::Error::other(err)
+    }
+}
+
 impl Error {
     fn wasi_error_code(err: ErrorCode) -> Self {
         Self::WasiErrorCode(err.to_string())
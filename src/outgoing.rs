@@ -1,10 +1,18 @@
 use std::{
     future::Future,
     pin::Pin,
-    task::{Context, Poll},
+    task::{ready, Context, Poll},
 };
 
-use crate::Error;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::{
+    coop,
+    incoming::{IncomingHttpBody, IncomingState},
+    poll::PollableRegistry,
+    wasi::{FieldEntries, OutgoingBody, OutputStream},
+    Error,
+};
 
 pub enum Copied {
     Body(usize),
@@ -29,8 +37,10 @@ impl<T: OutgoingBodyCopier + Unpin> Future for CopyAllFuture<T> {
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         loop {
+            ready!(coop::poll_proceed(cx));
             match self.0.poll_copy(cx) {
-                Poll::Ready(Some(Ok(_))) => (),
+                Poll::Ready(Some(Ok(Copied::Body(len)))) => coop::consume_bytes(len),
+                Poll::Ready(Some(Ok(Copied::Trailers))) => (),
                 Poll::Ready(Some(Err(err))) => return Poll::Ready(Err(err)),
                 Poll::Ready(None) => return Poll::Ready(Ok(())),
                 Poll::Pending => return Poll::Pending,
@@ -38,3 +48,322 @@ impl<T: OutgoingBodyCopier + Unpin> Future for CopyAllFuture<T> {
         }
     }
 }
+
+/// Data frames waiting to be written by a copier. A lone frame is written in
+/// place; once a second frame arrives they are gathered into one buffer so
+/// they go out in a single write.
+pub(crate) struct WriteGather<D> {
+    frame: Option<D>,
+    // Only used while frame is None
+    buf: BytesMut,
+    coalesce: usize,
+    // What check-write last allowed; cleared by each write
+    room: Option<u64>,
+}
+
+impl<D: Buf> WriteGather<D> {
+    pub(crate) fn new() -> Self {
+        Self {
+            frame: None,
+            buf: BytesMut::new(),
+            coalesce: 0,
+            room: None,
+        }
+    }
+
+    /// Holds data back until at least `threshold` bytes are waiting or the
+    /// source has ended.
+    pub(crate) fn set_coalesce_threshold(&mut self, threshold: usize) {
+        self.coalesce = threshold;
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match &self.frame {
+            Some(frame) => frame.remaining(),
+            None => self.buf.len(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn push(&mut self, data: D) {
+        if !data.has_remaining() {
+            return;
+        }
+        if self.is_empty() {
+            self.frame = Some(data);
+            return;
+        }
+        if let Some(frame) = self.frame.take() {
+            self.buf.put(frame);
+        }
+        self.buf.put(data);
+    }
+
+    /// Whether to read another frame before writing: true while below the
+    /// coalescing threshold or while the stream could take more than is
+    /// waiting.
+    pub(crate) fn wants_more<Registry>(
+        &mut self,
+        cx: &mut Context,
+        stream: &mut OutputStream<Registry>,
+    ) -> Result<bool, Error>
+    where
+        Registry: PollableRegistry,
+    {
+        let len = self.len();
+        if len == 0 || len < self.coalesce {
+            return Ok(true);
+        }
+        let room = match self.room {
+            Some(room) => room,
+            None => match stream.poll_check_write(cx)? {
+                Poll::Ready(permit) => *self.room.insert(permit.size()),
+                Poll::Pending => return Ok(false),
+            },
+        };
+        Ok(room > len as u64)
+    }
+
+    /// Whether to wait for the source rather than write what is waiting.
+    pub(crate) fn holding(&self) -> bool {
+        let len = self.len();
+        len == 0 || len < self.coalesce
+    }
+
+    pub(crate) fn poll_write<Registry>(
+        &mut self,
+        cx: &mut Context,
+        stream: &mut OutputStream<Registry>,
+    ) -> Poll<Result<usize, Error>>
+    where
+        Registry: PollableRegistry,
+    {
+        self.room = None;
+        match &mut self.frame {
+            Some(frame) => {
+                let len = ready!(stream.poll_write(cx, frame.chunk()))?;
+                frame.advance(len);
+                if !frame.has_remaining() {
+                    self.frame = None;
+                }
+                Poll::Ready(Ok(len))
+            }
+            None => {
+                let len = ready!(stream.poll_write(cx, &self.buf))?;
+                self.buf.advance(len);
+                Poll::Ready(Ok(len))
+            }
+        }
+    }
+}
+
+/// How much SpliceCopier asks the host to splice at once.
+const SPLICE_SIZE: u64 = 64 * 1024;
+
+/// Copies an [`IncomingHttpBody`] to an [`OutgoingBody`] using host-side
+/// splice, then forwards its trailers. Body data never passes through guest
+/// memory, which makes this the cheapest way to proxy a request or response.
+pub struct SpliceCopier<Registry, SrcRegistry = Registry>
+where
+    Registry: PollableRegistry,
+    SrcRegistry: PollableRegistry,
+{
+    src: IncomingHttpBody<SrcRegistry>,
+    // None once finished
+    dest: Option<OutgoingBody<Registry>>,
+}
+
+impl<Registry, SrcRegistry> SpliceCopier<Registry, SrcRegistry>
+where
+    Registry: PollableRegistry,
+    SrcRegistry: PollableRegistry,
+{
+    pub fn new(src: IncomingHttpBody<SrcRegistry>, dest: OutgoingBody<Registry>) -> Self {
+        Self {
+            src,
+            dest: Some(dest),
+        }
+    }
+}
+
+impl<Registry, SrcRegistry> OutgoingBodyCopier for SpliceCopier<Registry, SrcRegistry>
+where
+    Registry: PollableRegistry,
+    SrcRegistry: PollableRegistry,
+{
+    fn poll_copy(&mut self, cx: &mut Context) -> Poll<Option<Result<Copied, Error>>> {
+        let Some(dest) = &mut self.dest else {
+            return Poll::Ready(None);
+        };
+
+        if let IncomingState::Body(incoming_body) = &mut self.src.state {
+            match ready!(dest
+                .stream()
+                .poll_splice(cx, incoming_body.stream(), SPLICE_SIZE))
+            {
+                Ok(len) => return Poll::Ready(Some(Ok(Copied::Body(len as usize)))),
+                Err(Error::WasiStreamClosed) => {
+                    self.src.state = IncomingState::Trailers(self.src.take_body().finish());
+                }
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            }
+        }
+
+        let trailers = match ready!(self.src.poll_incoming_trailers(cx)) {
+            Ok(trailers) => trailers,
+            Err(err) => return Poll::Ready(Some(Err(err))),
+        };
+        let copied_trailers = trailers.is_some();
+        if let Err(err) = self.dest.take().unwrap().finish(trailers) {
+            return Poll::Ready(Some(Err(err)));
+        }
+        if copied_trailers {
+            Poll::Ready(Some(Ok(Copied::Trailers)))
+        } else {
+            Poll::Ready(None)
+        }
+    }
+}
+
+/// A [`Sink`](futures_sink::Sink) of body data that writes to an
+/// [`OutgoingBody`], finishing it with any trailers when closed.
+pub struct OutgoingBodySink<Registry: PollableRegistry> {
+    // None once closed
+    body: Option<OutgoingBody<Registry>>,
+    buf: Bytes,
+    trailers: Option<FieldEntries>,
+}
+
+impl<Registry> OutgoingBodySink<Registry>
+where
+    Registry: PollableRegistry,
+{
+    pub fn new(body: OutgoingBody<Registry>) -> Self {
+        Self {
+            body: Some(body),
+            buf: Bytes::new(),
+            trailers: None,
+        }
+    }
+
+    /// Sets the trailers the body is finished with when the sink is closed.
+    pub fn set_trailers(&mut self, trailers: FieldEntries) {
+        self.trailers = Some(trailers);
+    }
+
+    fn body(&mut self) -> Result<&mut OutgoingBody<Registry>, Error> {
+        self.body
+            .as_mut()
+            .ok_or(Error::WasiInvalidState("outgoing body sink already closed"))
+    }
+
+    /// Writes out any buffered data.
+    fn poll_write_buf(&mut self, cx: &mut Context) -> Poll<Result<(), Error>> {
+        while self.buf.has_remaining() {
+            ready!(coop::poll_proceed(cx));
+            let stream = self.body.as_mut().unwrap().stream();
+            let len = ready!(stream.poll_write(cx, &self.buf))?;
+            coop::consume_bytes(len);
+            self.buf.advance(len);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<Registry> futures_sink::Sink<Bytes> for OutgoingBodySink<Registry>
+where
+    Registry: PollableRegistry,
+{
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        let this = self.get_mut();
+        this.body()?;
+        this.poll_write_buf(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> Result<(), Error> {
+        let this = self.get_mut();
+        this.body()?;
+        if this.buf.has_remaining() {
+            return Err(Error::WasiInvalidState(
+                "outgoing body sink start_send called before poll_ready",
+            ));
+        }
+        this.buf = item;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        let this = self.get_mut();
+        this.body()?;
+        ready!(this.poll_write_buf(cx))?;
+        this.body()?.stream().poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        if self.body.is_none() {
+            return Poll::Ready(Ok(()));
+        }
+        ready!(self.as_mut().poll_flush(cx))?;
+        let this = self.get_mut();
+        let body = this.body.take().unwrap();
+        Poll::Ready(body.finish(this.trailers.take()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lone_frame_is_kept_in_place() {
+        let mut gather = WriteGather::new();
+        assert!(gather.is_empty());
+        let data = Bytes::from_static(b"hello");
+        gather.push(data.clone());
+        assert_eq!(gather.len(), 5);
+        assert_eq!(gather.frame, Some(data));
+        assert!(gather.buf.is_empty());
+    }
+
+    #[test]
+    fn later_frames_are_gathered() {
+        let mut gather = WriteGather::new();
+        gather.push(Bytes::from_static(b"hello"));
+        gather.push(Bytes::from_static(b", "));
+        gather.push(Bytes::from_static(b"world"));
+        assert_eq!(gather.len(), 12);
+        assert!(gather.frame.is_none());
+        assert_eq!(&gather.buf[..], b"hello, world");
+    }
+
+    #[test]
+    fn empty_frames_are_ignored() {
+        let mut gather = WriteGather::new();
+        gather.push(Bytes::new());
+        assert!(gather.is_empty());
+        assert!(gather.frame.is_none());
+        gather.push(Bytes::from_static(b"data"));
+        gather.push(Bytes::new());
+        assert_eq!(gather.frame, Some(Bytes::from_static(b"data")));
+    }
+
+    #[test]
+    fn holds_below_the_coalesce_threshold() {
+        let mut gather = WriteGather::new();
+        assert!(gather.holding());
+        gather.push(Bytes::from_static(b"data"));
+        assert!(!gather.holding());
+
+        let mut gather = WriteGather::new();
+        gather.set_coalesce_threshold(8);
+        gather.push(Bytes::from_static(b"data"));
+        assert!(gather.holding());
+        gather.push(Bytes::from_static(b"more"));
+        assert!(!gather.holding());
+    }
+}
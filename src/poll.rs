@@ -1,140 +1,966 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex, Weak},
+    cell::RefCell,
+    collections::VecDeque,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
     task::{Context, Poll, Wake, Waker},
+    time::Duration,
 };
 
-use wasi::io::poll::Pollable;
+use slab::Slab;
+use wasi::{
+    clocks::monotonic_clock::{subscribe_duration, Instant},
+    io::poll::Pollable,
+};
+
+use crate::{
+    coop::{self, Budget},
+    current, time,
+};
 
 /// A PollableRegistry manages the polling of Pollables in relation to some
 /// Rust async executor. This must be a cheaply-`clone`able handle to its
 /// underlying state.
-pub trait PollableRegistry: Clone + Unpin {
-    type RegisteredPollable: Unpin;
+pub trait PollableRegistry: Clone + Unpin + 'static {
+    type RegisteredPollable: Unpin + 'static;
 
     /// Registers the given pollable to be polled. When the pollable is ready
     /// the the given context's waker should be called. The pollable must be
     /// immediately dropped when the returned RegisteredPollable is dropped.
-    fn register_pollable(&self, cx: &mut Context, pollable: Pollable) -> Self::RegisteredPollable;
+    fn register_pollable(
+        &self,
+        cx: &mut Context,
+        pollable: Pollable,
+        kind: PollableKind,
+    ) -> Self::RegisteredPollable;
+
+    /// Re-arms a pollable registered with this registry so that the given
+    /// context's waker is called when it is next ready, without subscribing
+    /// to the resource again. Returns false if the registration can't be
+    /// re-armed, in which case the caller should register a new pollable.
+    fn rearm(&self, _cx: &mut Context, _registered: &mut Self::RegisteredPollable) -> bool {
+        false
+    }
 
     /// Poll all pollables. Returns false if there are no active pollables.
     fn poll(&self) -> bool;
 
+    /// Wakes any pollables that are already ready, and runs any other work
+    /// that can make progress, without blocking. Returns false if nothing was
+    /// woken or run.
+    fn try_poll(&self) -> bool {
+        false
+    }
+
+    /// Identifies the state shared by clones of this registry, so that
+    /// [`block_on`](Self::block_on) can detect being called re-entrantly.
+    /// Registries that wrap another should return the wrapped registry's id.
+    fn id(&self) -> Option<usize> {
+        None
+    }
+
+    /// A name identifying this registry in diagnostics.
+    fn name(&self) -> String {
+        std::any::type_name::<Self>().into()
+    }
+
+    /// The kinds of the most recently registered pollables, oldest first,
+    /// if this registry keeps track of them.
+    fn recent_registrations(&self) -> Vec<PollableKind> {
+        Vec::new()
+    }
+
+    /// Records time spent in a blocking call on a resource outside of
+    /// [`poll`](Self::poll), e.g. by [`std::io::Read`] on a body, so that it
+    /// isn't mistaken for time spent running Rust code.
+    fn record_blocking_call(&self, _kind: PollableKind, _duration: Duration) {}
+
+    /// Waits for any background work tied to this registry, such as scoped
+    /// tasks, to complete.
+    fn join_all(&self) -> Result<(), BlockOnError> {
+        Ok(())
+    }
+
     /// Runs the given future to completion, polling any WASI pollables that
-    /// are registered with this registry. Returns Err(Stalled) if there are no
-    /// active pollables while the future is pending.
-    fn block_on<T>(&self, fut: impl std::future::Future<Output = T>) -> Result<T, Stalled> {
+    /// are registered with this registry. The future is only re-polled after
+    /// its waker has been woken. This registry is the
+    /// [current registry](crate::current) while the future runs.
+    ///
+    /// Each poll of the future is given a [`Budget`]. Whenever the future
+    /// wakes itself, e.g. because it ran out of budget, ready pollables and
+    /// other work are checked without blocking before it is polled again.
+    ///
+    /// Returns Err(Stalled) if there are no active pollables while the future
+    /// is pending, Err(Spinning) if the future keeps waking itself without
+    /// completing, or Err(Reentrant) if this registry is already running
+    /// block_on on this thread, e.g. when called from inside an async handler.
+    fn block_on<T>(&self, fut: impl std::future::Future<Output = T>) -> Result<T, BlockOnError> {
+        // Polling the same pollables from a nested loop would wake the outer
+        // future's wakers out from under it
+        if self.id().is_some_and(current::is_entered) {
+            return Err(BlockOnError::Reentrant(self.name()));
+        }
+        let _guard = current::enter(self);
         let mut fut = std::pin::pin!(fut);
-        let waker = noop_waker();
+        let woken = Arc::new(WakeFlag(AtomicBool::new(true)));
+        let waker = Waker::from(woken.clone());
         let mut cx = Context::from_waker(&waker);
+        let mut self_wakes = 0;
+        let mut rounds = 0;
         loop {
-            if let Poll::Ready(val) = fut.as_mut().poll(&mut cx) {
-                return Ok(val);
+            if woken.take() {
+                let (poll, yielded) =
+                    coop::run_budgeted(Budget::default(), || fut.as_mut().poll(&mut cx));
+                if let Poll::Ready(val) = poll {
+                    return Ok(val);
+                }
+                if woken.is_set() {
+                    // Woken during its own poll; let anything else that is
+                    // ready catch up without blocking, then poll it again
+                    let progressed = self.try_poll();
+                    if progressed || yielded {
+                        self_wakes = 0;
+                    } else {
+                        self_wakes += 1;
+                        if self_wakes > MAX_SELF_WAKES {
+                            return Err(BlockOnError::Spinning(self_wakes));
+                        }
+                    }
+                    continue;
+                }
+                self_wakes = 0;
             }
             if !self.poll() {
-                return Err(Stalled);
+                return Err(Stalled::new(self.name(), rounds, self.recent_registrations()).into());
             }
+            rounds += 1;
         }
     }
+
+    /// Like [`block_on`](Self::block_on), but returns Err(TimedOut) if the
+    /// future has not completed when the monotonic clock reaches `deadline`.
+    fn block_on_with_deadline<T>(
+        &self,
+        deadline: Instant,
+        fut: impl std::future::Future<Output = T>,
+    ) -> Result<T, BlockOnError> {
+        self.block_on(time::timeout_at(deadline, fut, self.clone()))?
+            .map_err(|_| BlockOnError::TimedOut)
+    }
+
+    /// Like [`block_on`](Self::block_on), but returns Err(TimedOut) if the
+    /// future has not completed within `duration`.
+    fn block_on_timeout<T>(
+        &self,
+        duration: Duration,
+        fut: impl std::future::Future<Output = T>,
+    ) -> Result<T, BlockOnError> {
+        self.block_on_with_deadline(time::deadline_after(duration), fut)
+    }
+}
+
+/// The number of consecutive polls in which a future may wake itself before
+/// block_on gives up on it.
+const MAX_SELF_WAKES: usize = 10_000;
+
+struct WakeFlag(AtomicBool);
+
+impl WakeFlag {
+    fn take(&self) -> bool {
+        self.0.swap(false, Ordering::AcqRel)
+    }
+
+    fn is_set(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
 }
 
+impl Wake for WakeFlag {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// The default PollableRegistry. Registered pollables are kept in a slab so
+/// that several wakers may wait on the same resource. The armed ones are
+/// tracked as they are registered, re-armed, woken and dropped, so each poll
+/// only visits those, and ready pollables are woken in registration order.
 #[derive(Default)]
 pub struct Poller {
-    entries: Arc<Mutex<HashMap<u32, Entry>>>,
+    inner: Arc<Mutex<PollerState>>,
 }
 
-struct Entry {
-    pollable: Weak<Pollable>,
-    waker: Waker,
+/// The state shared by [`Poller`] and [`LocalPoller`]. Generic over the
+/// pollable so that its bookkeeping can be exercised off WASI.
+struct PollerState<P = Pollable> {
+    entries: Slab<Entry<P>>,
+    // (seq, key) of the entries with a waker, sorted by seq
+    armed: Vec<(u64, usize)>,
+    next_seq: u64,
+    name: Option<String>,
+    recent: VecDeque<PollableKind>,
+    hook: Option<Arc<RegistrationHook>>,
+    observer: Option<Arc<dyn PollObserver>>,
+    // When the last call to wasi:io/poll.poll returned
+    last_poll: Option<std::time::Instant>,
+    // Time spent in blocking calls since then
+    blocking_since_poll: Duration,
 }
 
-impl PollableRegistry for Poller {
-    type RegisteredPollable = Arc<Pollable>;
+impl<P> Default for PollerState<P> {
+    fn default() -> Self {
+        Self {
+            entries: Slab::new(),
+            armed: Vec::new(),
+            next_seq: 0,
+            name: None,
+            recent: VecDeque::new(),
+            hook: None,
+            observer: None,
+            last_poll: None,
+            blocking_since_poll: Duration::ZERO,
+        }
+    }
+}
+
+type RegistrationHook = dyn Fn(PollableKind) + Send + Sync;
+
+/// Receives events from a [`Poller`] or [`LocalPoller`], e.g. to collect
+/// metrics. See [`PollStats`](crate::stats::PollStats) for a ready-made
+/// implementation.
+pub trait PollObserver: Send + Sync {
+    /// Called when a pollable is registered.
+    fn on_register(&self, _kind: PollableKind) {}
+
+    /// Called after each call to `wasi:io/poll.poll`.
+    fn on_poll(&self, _event: &PollEvent) {}
+
+    /// Called after a blocking call on a resource made outside of a poll; see
+    /// [`PollableRegistry::record_blocking_call`].
+    fn on_blocking_call(&self, _kind: PollableKind, _duration: Duration) {}
+}
+
+/// Describes a single call to `wasi:io/poll.poll`.
+#[derive(Clone, Copy, Debug)]
+pub struct PollEvent {
+    /// The number of pollables polled.
+    pub pollables: usize,
+    /// The number of those pollables that were ready.
+    pub ready: usize,
+    /// Whether the call was allowed to block; see
+    /// [`PollableRegistry::try_poll`].
+    pub blocking: bool,
+    /// Time spent in the call.
+    pub blocked: Duration,
+    /// Time spent running Rust code since the previous call returned. Time
+    /// spent in blocking calls reported to the observer is excluded.
+    pub busy: Duration,
+}
+
+/// The result of registering with a PollerState, to be acted on once the
+/// state is no longer borrowed.
+struct Registered {
+    key: usize,
+    hook: Option<Arc<RegistrationHook>>,
+    observer: Option<Arc<dyn PollObserver>>,
+}
 
-    fn register_pollable(&self, cx: &mut Context, pollable: Pollable) -> Self::RegisteredPollable {
-        let handle = pollable.handle();
-        let pollable = Arc::new(pollable);
+impl Registered {
+    fn notify(self, kind: PollableKind) -> usize {
+        if let Some(hook) = self.hook {
+            hook(kind);
+        }
+        if let Some(observer) = self.observer {
+            observer.on_register(kind);
+        }
+        self.key
+    }
+}
+
+/// The result of polling a PollerState, to be acted on once the state is no
+/// longer borrowed.
+struct Polled {
+    wakers: Vec<Waker>,
+    event: PollEvent,
+    observer: Option<Arc<dyn PollObserver>>,
+}
+
+/// The number of registrations a Poller remembers for diagnostics.
+pub(crate) const RECENT_REGISTRATIONS: usize = 8;
+
+struct Entry<P> {
+    pollable: P,
+    // None once the entry has been woken
+    waker: Option<Waker>,
+    // Orders entries by registration, as slab keys are reused
+    seq: u64,
+}
+
+impl<P> PollerState<P> {
+    fn register(&mut self, cx: &mut Context, pollable: P, kind: PollableKind) -> Registered {
+        let seq = self.next_seq;
+        self.next_seq += 1;
         let entry = Entry {
-            pollable: Arc::downgrade(&pollable),
-            waker: cx.waker().clone(),
+            pollable,
+            waker: Some(cx.waker().clone()),
+            seq,
         };
-        self.entries.lock().unwrap().insert(handle, entry);
-        pollable
+        if self.recent.len() == RECENT_REGISTRATIONS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(kind);
+        let key = self.entries.insert(entry);
+        // seq is the highest yet, so this keeps armed sorted
+        self.armed.push((seq, key));
+        Registered {
+            key,
+            hook: self.hook.clone(),
+            observer: self.observer.clone(),
+        }
     }
 
-    fn poll(&self) -> bool {
-        let mut entries = self.entries.lock().unwrap();
+    fn remove(&mut self, key: usize) {
+        let entry = self.entries.remove(key);
+        if entry.waker.is_some() {
+            self.disarm(entry.seq);
+        }
+    }
 
-        // Remove any dropped pollables
-        entries.retain(|_, entry| entry.pollable.strong_count() > 0);
+    fn disarm(&mut self, seq: u64) {
+        if let Ok(idx) = self.armed.binary_search_by_key(&seq, |&(seq, _)| seq) {
+            self.armed.remove(idx);
+        }
+    }
 
-        if entries.is_empty() {
+    /// Re-arms an entry, only replacing its waker if it would wake a
+    /// different task.
+    fn rearm(&mut self, cx: &mut Context, key: usize) -> bool {
+        let Some(entry) = self.entries.get_mut(key) else {
             return false;
+        };
+        match &entry.waker {
+            Some(waker) if waker.will_wake(cx.waker()) => (),
+            Some(_) => entry.waker = Some(cx.waker().clone()),
+            None => {
+                entry.waker = Some(cx.waker().clone());
+                let seq = entry.seq;
+                let idx = self.armed.partition_point(|&(armed, _)| armed < seq);
+                self.armed.insert(idx, (seq, key));
+            }
+        }
+        true
+    }
+
+    /// Disarms the entries at the given indices into the armed list,
+    /// returning their wakers in registration order.
+    fn take_ready(&mut self, mut ready: Vec<usize>) -> Vec<Waker> {
+        ready.retain(|&idx| idx < self.armed.len());
+        ready.sort_unstable();
+        let wakers = ready
+            .iter()
+            .filter_map(|&idx| self.entries[self.armed[idx].1].waker.take())
+            .collect();
+        for &idx in ready.iter().rev() {
+            self.armed.remove(idx);
+        }
+        wakers
+    }
+
+    /// Records a blocking call, returning the observer to notify once the
+    /// state is no longer borrowed.
+    fn record_blocking_call(&mut self, duration: Duration) -> Option<Arc<dyn PollObserver>> {
+        self.blocking_since_poll += duration;
+        self.observer.clone()
+    }
+
+    fn name(&self, registry: &str) -> String {
+        match &self.name {
+            Some(name) => format!("{registry} {name:?}"),
+            None => registry.into(),
         }
+    }
+}
 
-        // Poll pollables
-        let pollables = entries
-            .values()
-            .filter_map(|entry| entry.pollable.upgrade())
+impl PollerState {
+    /// Polls armed entries, returning the wakers of those that are ready, or
+    /// None if there are no armed entries.
+    fn poll_ready(&mut self, block: bool) -> Option<Polled> {
+        if self.armed.is_empty() {
+            return None;
+        }
+        // The bindings take a slice of references, so these are gathered
+        // from the armed list for each call
+        let mut pollables = self
+            .armed
+            .iter()
+            .map(|&(_, key)| &self.entries[key].pollable)
             .collect::<Vec<_>>();
-        let pollable_refs = pollables.iter().map(|p| p.as_ref()).collect::<Vec<_>>();
-        let ready_idxs = wasi::io::poll::poll(&pollable_refs);
 
-        // Remove and wake any ready pollables
-        for idx in ready_idxs {
-            let idx: usize = idx.try_into().unwrap();
-            let handle = pollables[idx].handle();
-            let entry = entries.remove(&handle).unwrap();
-            entry.waker.wake();
+        // An already-elapsed timer keeps poll from blocking
+        let elapsed;
+        if !block {
+            elapsed = subscribe_duration(0);
+            pollables.push(&elapsed);
         }
-        true
+
+        let start = std::time::Instant::now();
+        let ready_idxs = wasi::io::poll::poll(&pollables);
+        let end = std::time::Instant::now();
+        drop(pollables);
+
+        let armed = self.armed.len();
+        let wakers = self.take_ready(
+            ready_idxs
+                .into_iter()
+                .map(|idx| usize::try_from(idx).unwrap())
+                .collect(),
+        );
+        let event = PollEvent {
+            pollables: armed,
+            ready: wakers.len(),
+            blocking: block,
+            blocked: end - start,
+            busy: self
+                .last_poll
+                .map(|last| (start - last).saturating_sub(self.blocking_since_poll))
+                .unwrap_or_default(),
+        };
+        self.last_poll = Some(end);
+        self.blocking_since_poll = Duration::ZERO;
+
+        Some(Polled {
+            wakers,
+            event,
+            observer: self.observer.clone(),
+        })
+    }
+}
+
+/// Wakes the ready entries, returning how many there were, or None if there
+/// were no armed entries to poll.
+fn wake_all(polled: Option<Polled>) -> Option<usize> {
+    let polled = polled?;
+    if let Some(observer) = polled.observer {
+        observer.on_poll(&polled.event);
+    }
+    let woken = polled.wakers.len();
+    for waker in polled.wakers {
+        waker.wake();
+    }
+    Some(woken)
+}
+
+impl Poller {
+    /// Returns a Poller identified by `name` in diagnostics.
+    pub fn named(name: impl Into<String>) -> Self {
+        let poller = Self::default();
+        poller.inner.lock().unwrap().name = Some(name.into());
+        poller
+    }
+
+    /// Sets a hook that is called with the kind of each registered pollable,
+    /// e.g. to log registrations while debugging a stalled future.
+    pub fn with_registration_hook(
+        self,
+        hook: impl Fn(PollableKind) + Send + Sync + 'static,
+    ) -> Self {
+        self.inner.lock().unwrap().hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets an observer that is told about each registration and poll.
+    pub fn with_observer(self, observer: impl PollObserver + 'static) -> Self {
+        self.inner.lock().unwrap().observer = Some(Arc::new(observer));
+        self
+    }
+}
+
+impl PollableRegistry for Poller {
+    type RegisteredPollable = Registration;
+
+    fn register_pollable(
+        &self,
+        cx: &mut Context,
+        pollable: Pollable,
+        kind: PollableKind,
+    ) -> Self::RegisteredPollable {
+        let registered = self.inner.lock().unwrap().register(cx, pollable, kind);
+        let key = registered.notify(kind);
+        Registration {
+            key,
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    fn rearm(&self, cx: &mut Context, registered: &mut Self::RegisteredPollable) -> bool {
+        registered.inner.ptr_eq(&Arc::downgrade(&self.inner))
+            && self.inner.lock().unwrap().rearm(cx, registered.key)
+    }
+
+    fn poll(&self) -> bool {
+        let polled = self.inner.lock().unwrap().poll_ready(true);
+        // Wake outside of the lock so wakers may register new pollables
+        wake_all(polled).is_some()
+    }
+
+    fn try_poll(&self) -> bool {
+        let polled = self.inner.lock().unwrap().poll_ready(false);
+        wake_all(polled).is_some_and(|woken| woken > 0)
+    }
+
+    fn record_blocking_call(&self, kind: PollableKind, duration: Duration) {
+        let observer = self.inner.lock().unwrap().record_blocking_call(duration);
+        if let Some(observer) = observer {
+            observer.on_blocking_call(kind, duration);
+        }
+    }
+
+    fn id(&self) -> Option<usize> {
+        Some(Arc::as_ptr(&self.inner) as usize)
+    }
+
+    fn name(&self) -> String {
+        self.inner.lock().unwrap().name("Poller")
+    }
+
+    fn recent_registrations(&self) -> Vec<PollableKind> {
+        self.inner.lock().unwrap().recent.iter().copied().collect()
     }
 }
 
 impl Clone for Poller {
     fn clone(&self) -> Self {
         Self {
-            entries: self.entries.clone(),
+            inner: self.inner.clone(),
         }
     }
 }
 
-pub fn noop_waker() -> Waker {
-    struct NoopWaker;
-    impl Wake for NoopWaker {
-        fn wake(self: Arc<Self>) {}
+/// A pollable registered with a [`Poller`]. The pollable is dropped along
+/// with its registration.
+pub struct Registration {
+    key: usize,
+    inner: Weak<Mutex<PollerState>>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.upgrade() {
+            inner.lock().unwrap().remove(self.key);
+        }
     }
-    Arc::new(NoopWaker).into()
 }
 
+/// A single-threaded [`Poller`] that keeps its state in an `Rc<RefCell<..>>`
+/// instead of an `Arc<Mutex<..>>`, so registering, re-arming and polling
+/// take no locks. `block_on`'s waker and any hook or observer are still
+/// `Arc`s. Its registrations are `!Send`, so bodies using it can't be passed
+/// to frameworks that require `Send` bodies.
+#[derive(Default)]
+pub struct LocalPoller {
+    inner: Rc<RefCell<PollerState>>,
+}
+
+impl LocalPoller {
+    /// Returns a LocalPoller identified by `name` in diagnostics.
+    pub fn named(name: impl Into<String>) -> Self {
+        let poller = Self::default();
+        poller.inner.borrow_mut().name = Some(name.into());
+        poller
+    }
+
+    /// Sets a hook that is called with the kind of each registered pollable.
+    pub fn with_registration_hook(
+        self,
+        hook: impl Fn(PollableKind) + Send + Sync + 'static,
+    ) -> Self {
+        self.inner.borrow_mut().hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets an observer that is told about each registration and poll.
+    pub fn with_observer(self, observer: impl PollObserver + 'static) -> Self {
+        self.inner.borrow_mut().observer = Some(Arc::new(observer));
+        self
+    }
+}
+
+impl PollableRegistry for LocalPoller {
+    type RegisteredPollable = LocalRegistration;
+
+    fn register_pollable(
+        &self,
+        cx: &mut Context,
+        pollable: Pollable,
+        kind: PollableKind,
+    ) -> Self::RegisteredPollable {
+        let registered = self.inner.borrow_mut().register(cx, pollable, kind);
+        let key = registered.notify(kind);
+        LocalRegistration {
+            key,
+            inner: Rc::downgrade(&self.inner),
+        }
+    }
+
+    fn rearm(&self, cx: &mut Context, registered: &mut Self::RegisteredPollable) -> bool {
+        registered.inner.ptr_eq(&Rc::downgrade(&self.inner))
+            && self.inner.borrow_mut().rearm(cx, registered.key)
+    }
+
+    fn poll(&self) -> bool {
+        let polled = self.inner.borrow_mut().poll_ready(true);
+        // Wake outside of the borrow so wakers may register new pollables
+        wake_all(polled).is_some()
+    }
+
+    fn try_poll(&self) -> bool {
+        let polled = self.inner.borrow_mut().poll_ready(false);
+        wake_all(polled).is_some_and(|woken| woken > 0)
+    }
+
+    fn record_blocking_call(&self, kind: PollableKind, duration: Duration) {
+        let observer = self.inner.borrow_mut().record_blocking_call(duration);
+        if let Some(observer) = observer {
+            observer.on_blocking_call(kind, duration);
+        }
+    }
+
+    fn id(&self) -> Option<usize> {
+        Some(Rc::as_ptr(&self.inner) as usize)
+    }
+
+    fn name(&self) -> String {
+        self.inner.borrow().name("LocalPoller")
+    }
+
+    fn recent_registrations(&self) -> Vec<PollableKind> {
+        self.inner.borrow().recent.iter().copied().collect()
+    }
+}
+
+impl Clone for LocalPoller {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// A pollable registered with a [`LocalPoller`].
+pub struct LocalRegistration {
+    key: usize,
+    inner: std::rc::Weak<RefCell<PollerState>>,
+}
+
+impl Drop for LocalRegistration {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.upgrade() {
+            inner.borrow_mut().remove(self.key);
+        }
+    }
+}
+
+pub fn noop_waker() -> Waker {
+    Waker::noop().clone()
+}
+
+/// Returned by [`PollableRegistry::block_on`] when the future is pending but
+/// there are no pollables left that could wake it. This usually means the
+/// future is waiting on a waker that is not driven by a WASI pollable, such as
+/// a channel whose sender is not being polled.
 #[derive(Debug)]
-pub struct Stalled;
+pub struct Stalled {
+    registry: String,
+    rounds: usize,
+    recent_registrations: Vec<PollableKind>,
+}
+
+impl Stalled {
+    pub(crate) fn new(
+        registry: String,
+        rounds: usize,
+        recent_registrations: Vec<PollableKind>,
+    ) -> Self {
+        Self {
+            registry,
+            rounds,
+            recent_registrations,
+        }
+    }
+
+    /// The name of the registry that stalled.
+    pub fn registry(&self) -> &str {
+        &self.registry
+    }
+
+    /// The number of poll rounds that ran before stalling.
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// The kinds of the last pollables registered before stalling, oldest
+    /// first.
+    pub fn recent_registrations(&self) -> &[PollableKind] {
+        &self.recent_registrations
+    }
+}
 
 impl std::fmt::Display for Stalled {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "future stalled with no remaining pollables")
+        write!(
+            f,
+            "future stalled with no remaining pollables ({} after {} poll rounds",
+            self.registry, self.rounds
+        )?;
+        if !self.recent_registrations.is_empty() {
+            let kinds = self
+                .recent_registrations
+                .iter()
+                .map(|kind| kind.to_string())
+                .collect::<Vec<_>>();
+            write!(f, "; last registered: {}", kinds.join(", "))?;
+        }
+        write!(f, "); is it waiting on a non-WASI waker?")
     }
 }
 
 impl std::error::Error for Stalled {}
 
+#[derive(Debug, thiserror::Error)]
+pub enum BlockOnError {
+    #[error(transparent)]
+    Stalled(#[from] Stalled),
+    #[error("future woke itself {0} times in a row without completing")]
+    Spinning(usize),
+    #[error("future did not complete before its deadline")]
+    TimedOut,
+    #[error("block_on called re-entrantly on {0}; await the future instead")]
+    Reentrant(String),
+}
+
+/// The kind of WASI resource a pollable was subscribed from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PollableKind {
+    InputStream,
+    OutputStream,
+    FutureTrailers,
+    FutureIncomingResponse,
+    MonotonicClock,
+    Other,
+}
+
+impl std::fmt::Display for PollableKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::InputStream => "input-stream",
+            Self::OutputStream => "output-stream",
+            Self::FutureTrailers => "future-trailers",
+            Self::FutureIncomingResponse => "future-incoming-response",
+            Self::MonotonicClock => "monotonic-clock",
+            Self::Other => "other",
+        })
+    }
+}
+
 pub trait WasiSubscribe: Unpin {
     fn subscribe(&self) -> wasi::io::poll::Pollable;
+
+    fn kind(&self) -> PollableKind {
+        PollableKind::Other
+    }
+}
+
+/// Returns a future that resolves to the index of the first of `resources`
+/// to become ready. If several are ready at once, the lowest index wins.
+///
+/// All of the resources are waited on by a single registry poll, so e.g.
+/// "first upstream to respond wins" or "read body or time out" need only one
+/// `wasi:io/poll.poll` call per round.
+pub fn select<'a, Registry>(
+    resources: &'a [&'a dyn WasiSubscribe],
+    registry: Registry,
+) -> Select<'a, Registry>
+where
+    Registry: PollableRegistry,
+{
+    Select {
+        waiting: Waiting::new(resources, registry),
+    }
+}
+
+/// Returns a future that resolves once all of `resources` are ready, waiting
+/// on all of those not yet ready with each registry poll.
+pub fn join<'a, Registry>(
+    resources: &'a [&'a dyn WasiSubscribe],
+    registry: Registry,
+) -> Join<'a, Registry>
+where
+    Registry: PollableRegistry,
+{
+    Join {
+        waiting: Waiting::new(resources, registry),
+    }
+}
+
+/// Returned by [`select`].
+pub struct Select<'a, Registry: PollableRegistry> {
+    waiting: Waiting<'a, Registry>,
+}
+
+impl<Registry> std::future::Future for Select<'_, Registry>
+where
+    Registry: PollableRegistry,
+{
+    type Output = usize;
+
+    fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.waiting.poll_ready(cx).iter().position(|&ready| ready) {
+            Some(idx) => Poll::Ready(idx),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Returned by [`join`].
+pub struct Join<'a, Registry: PollableRegistry> {
+    waiting: Waiting<'a, Registry>,
+}
+
+impl<Registry> std::future::Future for Join<'_, Registry>
+where
+    Registry: PollableRegistry,
+{
+    type Output = ();
+
+    fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.waiting.poll_ready(cx).iter().all(|&ready| ready) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+struct Waiting<'a, Registry: PollableRegistry> {
+    resources: &'a [&'a dyn WasiSubscribe],
+    handles: Vec<Option<Registry::RegisteredPollable>>,
+    ready: Arc<ReadySet>,
+    registry: Registry,
+}
+
+struct ReadySet {
+    state: Mutex<ReadyState>,
+}
+
+struct ReadyState {
+    ready: Vec<bool>,
+    waker: Option<Waker>,
+}
+
+/// Marks one resource as ready and wakes the task waiting on the set.
+struct ReadyWaker {
+    idx: usize,
+    set: Arc<ReadySet>,
+}
+
+impl Wake for ReadyWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let waker = {
+            let mut state = self.set.state.lock().unwrap();
+            state.ready[self.idx] = true;
+            state.waker.clone()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<'a, Registry> Waiting<'a, Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn new(resources: &'a [&'a dyn WasiSubscribe], registry: Registry) -> Self {
+        let ready = ReadySet {
+            state: Mutex::new(ReadyState {
+                ready: vec![false; resources.len()],
+                waker: None,
+            }),
+        };
+        Self {
+            resources,
+            handles: resources.iter().map(|_| None).collect(),
+            ready: Arc::new(ready),
+            registry,
+        }
+    }
+
+    /// Registers every resource that isn't ready yet, returning which are.
+    fn poll_ready(&mut self, cx: &mut Context) -> Vec<bool> {
+        let mut ready = {
+            let mut state = self.ready.state.lock().unwrap();
+            match &state.waker {
+                Some(waker) if waker.will_wake(cx.waker()) => (),
+                _ => state.waker = Some(cx.waker().clone()),
+            }
+            state.ready.clone()
+        };
+        for (idx, resource) in self.resources.iter().enumerate() {
+            if ready[idx] {
+                self.handles[idx] = None;
+                continue;
+            }
+            if self.handles[idx].is_some() {
+                continue;
+            }
+            let pollable = resource.subscribe();
+            if pollable.ready() {
+                ready[idx] = true;
+                self.ready.state.lock().unwrap().ready[idx] = true;
+                continue;
+            }
+            let waker = Waker::from(Arc::new(ReadyWaker {
+                idx,
+                set: self.ready.clone(),
+            }));
+            let mut cx = Context::from_waker(&waker);
+            let handle = self
+                .registry
+                .register_pollable(&mut cx, pollable, resource.kind());
+            self.handles[idx] = Some(handle);
+        }
+        ready
+    }
 }
 
 macro_rules! impl_subscribe {
-    ($($ty:ty),+) => {
+    ($($ty:ident),+) => {
         $(
             impl WasiSubscribe for $ty {
                 fn subscribe(&self) -> wasi::io::poll::Pollable {
                     self.subscribe()
                 }
+
+                fn kind(&self) -> PollableKind {
+                    PollableKind::$ty
+                }
             }
         )+
     }
 }
 mod subscribe_impls {
-    use super::WasiSubscribe;
+    use super::{PollableKind, WasiSubscribe};
     use wasi::http::types::*;
     impl_subscribe!(
         FutureTrailers,
@@ -143,3 +969,73 @@ mod subscribe_impls {
         FutureIncomingResponse
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register(state: &mut PollerState<()>, cx: &mut Context) -> usize {
+        state.register(cx, (), PollableKind::Other).key
+    }
+
+    #[test]
+    fn wakes_in_registration_order_when_slots_are_reused() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut state = PollerState::default();
+        let first = register(&mut state, &mut cx);
+        let second = register(&mut state, &mut cx);
+        state.remove(first);
+        // Reuses first's slot, but was registered last
+        let third = register(&mut state, &mut cx);
+        assert_eq!(third, first);
+
+        let order = state.armed.iter().map(|&(_, key)| key).collect::<Vec<_>>();
+        assert_eq!(order, [second, third]);
+        assert_eq!(state.take_ready(vec![1, 0]).len(), 2);
+        assert!(state.armed.is_empty());
+
+        // Re-arming restores registration order
+        assert!(state.rearm(&mut cx, third));
+        assert!(state.rearm(&mut cx, second));
+        let order = state.armed.iter().map(|&(_, key)| key).collect::<Vec<_>>();
+        assert_eq!(order, [second, third]);
+    }
+
+    #[test]
+    fn removing_disarms() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut state = PollerState::default();
+        let key = register(&mut state, &mut cx);
+        let other = register(&mut state, &mut cx);
+        state.remove(key);
+        assert_eq!(state.armed, [(1, other)]);
+        // Out of range indices are ignored
+        assert_eq!(state.take_ready(vec![0, 5]).len(), 1);
+        assert!(!state.rearm(&mut cx, key));
+    }
+
+    #[test]
+    fn stalled_display() {
+        let stalled = Stalled {
+            registry: "Poller \"api\"".into(),
+            rounds: 3,
+            recent_registrations: vec![],
+        };
+        assert_eq!(
+            stalled.to_string(),
+            "future stalled with no remaining pollables (Poller \"api\" after 3 poll rounds); \
+             is it waiting on a non-WASI waker?"
+        );
+
+        let stalled = Stalled {
+            registry: "Poller".into(),
+            rounds: 0,
+            recent_registrations: vec![PollableKind::InputStream, PollableKind::MonotonicClock],
+        };
+        assert_eq!(
+            BlockOnError::from(stalled).to_string(),
+            "future stalled with no remaining pollables (Poller after 0 poll rounds; \
+             last registered: input-stream, monotonic-clock); is it waiting on a non-WASI waker?"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::poll::{PollEvent, PollObserver, PollableKind};
+
+/// A [`PollObserver`] that totals what it observes. Clones share the same
+/// totals, so one clone can be given to a Poller and another kept to
+/// [`report`](Self::report) on, e.g. with
+/// [`ServiceOptions::report_poll_stats`](crate::hyperium1::ServiceOptions::report_poll_stats).
+#[derive(Default)]
+pub struct PollStats {
+    summary: Arc<Mutex<PollSummary>>,
+    reporter: Option<Arc<Reporter>>,
+}
+
+type Reporter = dyn Fn(&PollSummary) + Send + Sync;
+
+impl PollStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how [`report`](Self::report) emits the summary. By default it is
+    /// written to stderr.
+    pub fn with_reporter(
+        mut self,
+        reporter: impl Fn(&PollSummary) + Send + Sync + 'static,
+    ) -> Self {
+        self.reporter = Some(Arc::new(reporter));
+        self
+    }
+
+    /// Returns the totals so far.
+    pub fn summary(&self) -> PollSummary {
+        self.summary.lock().unwrap().clone()
+    }
+
+    /// Emits a summary of the totals so far.
+    pub fn report(&self) {
+        let summary = self.summary();
+        match &self.reporter {
+            Some(reporter) => reporter(&summary),
+            None => eprintln!("{summary}"),
+        }
+    }
+}
+
+impl PollObserver for PollStats {
+    fn on_register(&self, kind: PollableKind) {
+        let mut summary = self.summary.lock().unwrap();
+        *summary.registrations.entry(kind).or_default() += 1;
+    }
+
+    fn on_poll(&self, event: &PollEvent) {
+        let mut summary = self.summary.lock().unwrap();
+        summary.polls += 1;
+        summary.pollables += event.pollables as u64;
+        summary.max_pollables = summary.max_pollables.max(event.pollables);
+        summary.ready += event.ready as u64;
+        summary.blocked += event.blocked;
+        summary.busy += event.busy;
+    }
+
+    fn on_blocking_call(&self, _kind: PollableKind, duration: Duration) {
+        let mut summary = self.summary.lock().unwrap();
+        summary.blocking_calls += 1;
+        summary.blocking_call_time += duration;
+    }
+}
+
+impl Clone for PollStats {
+    fn clone(&self) -> Self {
+        Self {
+            summary: self.summary.clone(),
+            reporter: self.reporter.clone(),
+        }
+    }
+}
+
+impl std::fmt::Debug for PollStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PollStats")
+            .field("summary", &self.summary())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Totals collected by [`PollStats`].
+#[derive(Clone, Debug, Default)]
+pub struct PollSummary {
+    /// Calls to `wasi:io/poll.poll`.
+    pub polls: u64,
+    /// Pollables passed to those calls.
+    pub pollables: u64,
+    /// The most pollables passed to a single call.
+    pub max_pollables: usize,
+    /// Pollables that were ready when those calls returned.
+    pub ready: u64,
+    /// Time spent in those calls.
+    pub blocked: Duration,
+    /// Time spent running Rust code between those calls.
+    pub busy: Duration,
+    /// Blocking calls made on resources outside of those calls, e.g. by
+    /// `std::io::Read` on a body.
+    pub blocking_calls: u64,
+    /// Time spent in those blocking calls.
+    pub blocking_call_time: Duration,
+    /// Registrations per resource kind.
+    pub registrations: BTreeMap<PollableKind, u64>,
+}
+
+impl std::fmt::Display for PollSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let avg = match self.polls {
+            0 => 0.0,
+            polls => self.pollables as f64 / polls as f64,
+        };
+        write!(
+            f,
+            "{} polls ({avg:.1} pollables avg, {} max, {} ready); blocked {:?}, busy {:?}",
+            self.polls, self.max_pollables, self.ready, self.blocked, self.busy
+        )?;
+        if self.blocking_calls > 0 {
+            write!(
+                f,
+                "; {} blocking calls ({:?})",
+                self.blocking_calls, self.blocking_call_time
+            )?;
+        }
+        if !self.registrations.is_empty() {
+            let kinds = self
+                .registrations
+                .iter()
+                .map(|(kind, count)| format!("{kind}={count}"))
+                .collect::<Vec<_>>();
+            write!(f, "; registrations: {}", kinds.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_summary_display() {
+        assert_eq!(
+            PollSummary::default().to_string(),
+            "0 polls (0.0 pollables avg, 0 max, 0 ready); blocked 0ns, busy 0ns"
+        );
+    }
+
+    #[test]
+    fn collects_and_displays_totals() {
+        let stats = PollStats::new();
+        stats.on_register(PollableKind::InputStream);
+        stats.on_register(PollableKind::InputStream);
+        stats.on_register(PollableKind::OutputStream);
+        for pollables in [1, 2] {
+            stats.on_poll(&PollEvent {
+                pollables,
+                ready: 1,
+                blocking: true,
+                blocked: Duration::from_millis(2),
+                busy: Duration::from_millis(1),
+            });
+        }
+        stats.on_blocking_call(PollableKind::InputStream, Duration::from_millis(5));
+
+        // Clones share the same totals
+        let summary = stats.clone().summary();
+        assert_eq!(summary.polls, 2);
+        assert_eq!(summary.max_pollables, 2);
+        assert_eq!(
+            summary.to_string(),
+            "2 polls (1.5 pollables avg, 2 max, 2 ready); blocked 4ms, busy 2ms; \
+             1 blocking calls (5ms); registrations: input-stream=2, output-stream=1"
+        );
+    }
+
+    #[test]
+    fn reports_with_the_reporter() {
+        let reported = Arc::new(Mutex::new(None));
+        let stats = PollStats::new().with_reporter({
+            let reported = reported.clone();
+            move |summary| *reported.lock().unwrap() = Some(summary.polls)
+        });
+        stats.on_poll(&PollEvent {
+            pollables: 1,
+            ready: 0,
+            blocking: false,
+            blocked: Duration::ZERO,
+            busy: Duration::ZERO,
+        });
+        stats.report();
+        assert_eq!(*reported.lock().unwrap(), Some(1));
+    }
+}
@@ -0,0 +1,511 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+
+use futures_util::future::{abortable, AbortHandle};
+use wasi::io::poll::Pollable;
+
+use crate::{
+    coop::{self, Budget},
+    poll::{BlockOnError, PollableKind, PollableRegistry, Poller},
+};
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// An Executor runs spawned tasks alongside the future given to
+/// [`PollableRegistry::block_on`]. WASI pollables, and the event loop itself,
+/// are delegated to the wrapped registry.
+///
+/// Tasks spawned with [`Executor::spawn`] are cancelled when their
+/// [`JoinHandle`] is dropped. Tasks spawned with [`Executor::spawn_scoped`]
+/// keep running until they complete and are waited on by
+/// [`PollableRegistry::join_all`].
+///
+/// While `block_on` runs, the [current registry](crate::current) is the
+/// wrapped registry, not the Executor, so code that needs to spawn tasks must
+/// be given a clone of the Executor.
+pub struct Executor<Registry: PollableRegistry = Poller> {
+    registry: Registry,
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    tasks: Mutex<Tasks>,
+    ready: Mutex<VecDeque<u64>>,
+    // Woken along with tasks so that block_on runs them
+    root_waker: Mutex<Option<Waker>>,
+}
+
+impl Shared {
+    /// Queues a task to run, waking block_on to run it.
+    fn schedule(&self, id: u64) {
+        self.ready.lock().unwrap().push_back(id);
+        let root_waker = self.root_waker.lock().unwrap().clone();
+        if let Some(waker) = root_waker {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tasks {
+    next_id: u64,
+    entries: HashMap<u64, Task>,
+    scoped: usize,
+    scope_waker: Option<Waker>,
+}
+
+struct Task {
+    // None while the task is being polled
+    future: Option<BoxFuture>,
+    scoped: bool,
+}
+
+impl<Registry> Executor<Registry>
+where
+    Registry: PollableRegistry,
+{
+    pub fn new(registry: Registry) -> Self {
+        Self {
+            registry,
+            shared: Default::default(),
+        }
+    }
+
+    /// Spawns a task onto this executor. The task is cancelled if the
+    /// returned handle is dropped before the task completes.
+    pub fn spawn<F>(&self, fut: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_task(fut, false)
+    }
+
+    /// Spawns a task onto this executor that runs to completion even if the
+    /// returned handle is dropped.
+    pub fn spawn_scoped<F>(&self, fut: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_task(fut, true)
+    }
+
+    fn spawn_task<F>(&self, fut: F, scoped: bool) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(JoinState {
+            output: None,
+            waker: None,
+        }));
+        let (fut, abort) = abortable(fut);
+        let task_state = state.clone();
+        let future = Box::pin(async move {
+            // Cancelled tasks complete without output
+            let Ok(output) = fut.await else {
+                return;
+            };
+            let waker = {
+                let mut state = task_state.lock().unwrap();
+                state.output = Some(output);
+                state.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+
+        let id = {
+            let mut tasks = self.shared.tasks.lock().unwrap();
+            let id = tasks.next_id;
+            tasks.next_id += 1;
+            if scoped {
+                tasks.scoped += 1;
+            }
+            let task = Task {
+                future: Some(future),
+                scoped,
+            };
+            tasks.entries.insert(id, task);
+            id
+        };
+        self.shared.schedule(id);
+
+        JoinHandle {
+            state,
+            abort,
+            scoped,
+        }
+    }
+
+    fn task_waker(&self, id: u64) -> Waker {
+        Arc::new(TaskWaker {
+            id,
+            shared: Arc::downgrade(&self.shared),
+        })
+        .into()
+    }
+
+    /// Polls every task that has been woken. Returns false if no tasks were
+    /// ready to run.
+    fn run_ready(&self) -> bool {
+        let ready = std::mem::take(&mut *self.shared.ready.lock().unwrap());
+        if ready.is_empty() {
+            return false;
+        }
+
+        for id in ready {
+            let future = {
+                let mut tasks = self.shared.tasks.lock().unwrap();
+                tasks
+                    .entries
+                    .get_mut(&id)
+                    .and_then(|task| task.future.take())
+            };
+            // Tasks may be woken more than once or after completion
+            let Some(mut future) = future else {
+                continue;
+            };
+
+            let waker = self.task_waker(id);
+            let mut cx = Context::from_waker(&waker);
+            let done =
+                coop::with_budget(Budget::default(), || future.as_mut().poll(&mut cx)).is_ready();
+
+            let mut tasks = self.shared.tasks.lock().unwrap();
+            if !done {
+                if let Some(task) = tasks.entries.get_mut(&id) {
+                    task.future = Some(future);
+                }
+                continue;
+            }
+            let Some(task) = tasks.entries.remove(&id) else {
+                continue;
+            };
+            if task.scoped {
+                tasks.scoped -= 1;
+                if tasks.scoped == 0 {
+                    let scope_waker = tasks.scope_waker.take();
+                    drop(tasks);
+                    if let Some(waker) = scope_waker {
+                        waker.wake();
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+impl<Registry> PollableRegistry for Executor<Registry>
+where
+    Registry: PollableRegistry,
+{
+    type RegisteredPollable = Registry::RegisteredPollable;
+
+    fn register_pollable(
+        &self,
+        cx: &mut Context,
+        pollable: Pollable,
+        kind: PollableKind,
+    ) -> Self::RegisteredPollable {
+        self.registry.register_pollable(cx, pollable, kind)
+    }
+
+    fn rearm(&self, cx: &mut Context, registered: &mut Self::RegisteredPollable) -> bool {
+        self.registry.rearm(cx, registered)
+    }
+
+    /// Runs `fut` with the wrapped registry's `block_on`, running any woken
+    /// tasks each time `fut` is polled.
+    fn block_on<T>(&self, fut: impl Future<Output = T>) -> Result<T, BlockOnError> {
+        let mut fut = std::pin::pin!(fut);
+        self.registry.block_on(std::future::poll_fn(|cx| {
+            {
+                let mut root_waker = self.shared.root_waker.lock().unwrap();
+                match &*root_waker {
+                    Some(waker) if waker.will_wake(cx.waker()) => (),
+                    _ => *root_waker = Some(cx.waker().clone()),
+                }
+            }
+            let ran = self.run_ready();
+            if let Poll::Ready(val) = fut.as_mut().poll(cx) {
+                return Poll::Ready(val);
+            }
+            if ran && !self.shared.ready.lock().unwrap().is_empty() {
+                // Tasks woke themselves; come back to them without counting
+                // it as the root future spinning
+                coop::note_yield();
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        }))
+    }
+
+    fn poll(&self) -> bool {
+        // Only block on pollables when no tasks are ready to make progress,
+        // but still check them so busy tasks can't starve waiting ones
+        if self.run_ready() {
+            self.registry.try_poll();
+            true
+        } else {
+            self.registry.poll()
+        }
+    }
+
+    fn try_poll(&self) -> bool {
+        let ran = self.run_ready();
+        self.registry.try_poll() || ran
+    }
+
+    fn record_blocking_call(&self, kind: PollableKind, duration: Duration) {
+        self.registry.record_blocking_call(kind, duration)
+    }
+
+    fn join_all(&self) -> Result<(), BlockOnError> {
+        let shared = self.shared.clone();
+        self.block_on(std::future::poll_fn(move |cx| {
+            let mut tasks = shared.tasks.lock().unwrap();
+            if tasks.scoped == 0 {
+                Poll::Ready(())
+            } else {
+                tasks.scope_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }))?;
+        self.registry.join_all()
+    }
+
+    fn id(&self) -> Option<usize> {
+        self.registry.id()
+    }
+
+    fn name(&self) -> String {
+        format!("Executor({})", self.registry.name())
+    }
+
+    fn recent_registrations(&self) -> Vec<PollableKind> {
+        self.registry.recent_registrations()
+    }
+}
+
+impl<Registry> Clone for Executor<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn clone(&self) -> Self {
+        Self {
+            registry: self.registry.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<Registry> Default for Executor<Registry>
+where
+    Registry: PollableRegistry + Default,
+{
+    fn default() -> Self {
+        Self::new(Registry::default())
+    }
+}
+
+struct TaskWaker {
+    id: u64,
+    shared: Weak<Shared>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if let Some(shared) = self.shared.upgrade() {
+            shared.schedule(self.id);
+        }
+    }
+}
+
+struct JoinState<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// A handle to a task spawned on an [`Executor`], which resolves to the
+/// task's output.
+pub struct JoinHandle<T> {
+    state: Arc<Mutex<JoinState<T>>>,
+    abort: AbortHandle,
+    scoped: bool,
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match state.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        if !self.scoped {
+            // Aborting wakes the task so the executor drops it promptly
+            self.abort.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// A registry with nothing to wait on, so the executor can be driven off
+    /// WASI.
+    #[derive(Clone, Default)]
+    struct NoPollables;
+
+    impl PollableRegistry for NoPollables {
+        type RegisteredPollable = ();
+
+        fn register_pollable(&self, _cx: &mut Context, _pollable: Pollable, _kind: PollableKind) {
+            unreachable!("no pollables off WASI")
+        }
+
+        fn poll(&self) -> bool {
+            false
+        }
+    }
+
+    fn yield_now() -> impl Future<Output = ()> {
+        let mut yielded = false;
+        std::future::poll_fn(move |cx| {
+            if yielded {
+                return Poll::Ready(());
+            }
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+    }
+
+    #[test]
+    fn joins_spawned_tasks() {
+        let executor = Executor::new(NoPollables);
+        let a = executor.spawn(async {
+            yield_now().await;
+            1
+        });
+        let b = executor.spawn(async { 2 });
+        let sum = executor.block_on(async { a.await + b.await }).unwrap();
+        assert_eq!(sum, 3);
+    }
+
+    #[test]
+    fn runs_tasks_spawned_inside_block_on() {
+        let executor = Executor::new(NoPollables);
+        let sum = executor
+            .block_on(async {
+                let a = executor.spawn(async { 1 });
+                let b = executor.spawn(async {
+                    yield_now().await;
+                    2
+                });
+                a.await + b.await
+            })
+            .unwrap();
+        assert_eq!(sum, 3);
+    }
+
+    /// More than block_on lets a future wake itself in a row without
+    /// anything else making progress.
+    const MAX_YIELDS: usize = 20_000;
+
+    #[test]
+    fn root_can_wait_on_a_yielding_task() {
+        let executor = Executor::<NoPollables>::default();
+        let done = Arc::new(AtomicBool::new(false));
+        let _task = executor.spawn({
+            let done = done.clone();
+            async move {
+                for _ in 0..MAX_YIELDS {
+                    yield_now().await;
+                }
+                done.store(true, Ordering::Relaxed);
+            }
+        });
+        // Yields rather than awaiting the handle, so only the executor's
+        // progress keeps it from being counted as spinning
+        executor
+            .block_on(async {
+                while !done.load(Ordering::Relaxed) {
+                    yield_now().await;
+                }
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn dropped_handles_cancel_tasks() {
+        let executor = Executor::new(NoPollables);
+        let polled = Arc::new(AtomicUsize::new(0));
+        let handle = executor.spawn({
+            let polled = polled.clone();
+            async move {
+                loop {
+                    polled.fetch_add(1, Ordering::Relaxed);
+                    yield_now().await;
+                }
+            }
+        });
+        executor.block_on(yield_now()).unwrap();
+        drop(handle);
+        let before = polled.load(Ordering::Relaxed);
+        executor.block_on(yield_now()).unwrap();
+        executor.block_on(yield_now()).unwrap();
+        assert_eq!(polled.load(Ordering::Relaxed), before);
+        assert!(executor.shared.tasks.lock().unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn join_all_waits_for_scoped_tasks() {
+        let executor = Executor::new(NoPollables);
+        let done = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            let done = done.clone();
+            drop(executor.spawn_scoped(async move {
+                yield_now().await;
+                done.fetch_add(1, Ordering::Relaxed);
+            }));
+        }
+        executor.join_all().unwrap();
+        assert_eq!(done.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn stalls_when_nothing_can_wake_the_future() {
+        let executor = Executor::new(NoPollables);
+        let _task = executor.spawn(std::future::pending::<()>());
+        let err = executor.block_on(std::future::pending::<()>()).unwrap_err();
+        assert!(matches!(err, BlockOnError::Stalled(_)), "{err}");
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use wasi::clocks::monotonic_clock::{self, Instant};
+
+use crate::poll::{PollableKind, PollableRegistry, WasiSubscribe};
+
+/// Returns a future that completes after `duration` has elapsed.
+pub fn sleep<Registry>(duration: Duration, registry: Registry) -> Sleep<Registry>
+where
+    Registry: PollableRegistry,
+{
+    sleep_until(deadline_after(duration), registry)
+}
+
+/// Returns a future that completes once the monotonic clock reaches
+/// `deadline`.
+pub fn sleep_until<Registry>(deadline: Instant, registry: Registry) -> Sleep<Registry>
+where
+    Registry: PollableRegistry,
+{
+    Sleep {
+        deadline,
+        handle: None,
+        registry,
+    }
+}
+
+/// Runs `fut` to completion, returning `Err(Elapsed)` if it does not complete
+/// within `duration`.
+pub fn timeout<F, Registry>(duration: Duration, fut: F, registry: Registry) -> Timeout<F, Registry>
+where
+    F: Future,
+    Registry: PollableRegistry,
+{
+    timeout_at(deadline_after(duration), fut, registry)
+}
+
+/// Runs `fut` to completion, returning `Err(Elapsed)` if it does not complete
+/// before the monotonic clock reaches `deadline`.
+pub fn timeout_at<F, Registry>(
+    deadline: Instant,
+    fut: F,
+    registry: Registry,
+) -> Timeout<F, Registry>
+where
+    F: Future,
+    Registry: PollableRegistry,
+{
+    Timeout {
+        fut,
+        sleep: sleep_until(deadline, registry),
+    }
+}
+
+pub(crate) fn deadline_after(duration: Duration) -> Instant {
+    let nanos = duration.as_nanos().try_into().unwrap_or(u64::MAX);
+    monotonic_clock::now().saturating_add(nanos)
+}
+
+pub struct Sleep<Registry: PollableRegistry> {
+    deadline: Instant,
+    handle: Option<Registry::RegisteredPollable>,
+    registry: Registry,
+}
+
+impl<Registry> Sleep<Registry>
+where
+    Registry: PollableRegistry,
+{
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+}
+
+impl<Registry> Future for Sleep<Registry>
+where
+    Registry: PollableRegistry,
+{
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if monotonic_clock::now() >= self.deadline {
+            self.handle = None;
+            return Poll::Ready(());
+        }
+        let this = &mut *self;
+        if let Some(handle) = &mut this.handle {
+            if this.registry.rearm(cx, handle) {
+                return Poll::Pending;
+            }
+        }
+        let pollable = monotonic_clock::subscribe_instant(self.deadline);
+        let handle = self
+            .registry
+            .register_pollable(cx, pollable, PollableKind::MonotonicClock);
+        self.handle = Some(handle);
+        Poll::Pending
+    }
+}
+
+impl<Registry> WasiSubscribe for Sleep<Registry>
+where
+    Registry: PollableRegistry,
+{
+    fn subscribe(&self) -> wasi::io::poll::Pollable {
+        monotonic_clock::subscribe_instant(self.deadline)
+    }
+
+    fn kind(&self) -> PollableKind {
+        PollableKind::MonotonicClock
+    }
+}
+
+pin_project_lite::pin_project! {
+    pub struct Timeout<F, Registry: PollableRegistry> {
+        #[pin]
+        fut: F,
+        sleep: Sleep<Registry>,
+    }
+}
+
+impl<F, Registry> Future for Timeout<F, Registry>
+where
+    F: Future,
+    Registry: PollableRegistry,
+{
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Poll::Ready(val) = this.fut.poll(cx) {
+            return Poll::Ready(Ok(val));
+        }
+        Pin::new(this.sleep).poll(cx).map(|()| Err(Elapsed))
+    }
+}
+
+#[derive(Debug)]
+pub struct Elapsed;
+
+impl std::fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
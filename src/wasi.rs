@@ -1,12 +1,12 @@
 use std::{
     future::{Future, IntoFuture},
-    task::{Context, Poll},
+    task::{ready, Context, Poll},
 };
 
-use wasi::http::types;
+use wasi::{http::types, io::poll::Pollable};
 
 use crate::{
-    poll::{PollableRegistry, WasiSubscribe},
+    poll::{PollableKind, PollableRegistry, WasiSubscribe},
     Error,
 };
 
@@ -30,9 +30,17 @@ where
         }
     }
 
+    /// Waits for the resource to become ready, re-arming the existing
+    /// subscription if there is one.
     fn register_subscribe(&mut self, cx: &mut Context) {
+        if let Some(handle) = &mut self.handle {
+            if self.registry.rearm(cx, handle) {
+                return;
+            }
+        }
         let pollable = self.inner.subscribe();
-        self.handle = Some(self.registry.register_pollable(cx, pollable));
+        let kind = self.inner.kind();
+        self.handle = Some(self.registry.register_pollable(cx, pollable, kind));
     }
 
     fn maybe_subscribe(&mut self, cx: &mut Context) -> Poll<()> {
@@ -40,16 +48,64 @@ where
         if pollable.ready() {
             Poll::Ready(())
         } else {
-            self.handle = Some(self.registry.register_pollable(cx, pollable));
+            let kind = self.inner.kind();
+            self.handle = Some(self.registry.register_pollable(cx, pollable, kind));
             Poll::Pending
         }
     }
 
+    /// Runs a call to one of the resource's blocking bindings, reporting the
+    /// time spent in it to the registry.
+    fn blocking<U>(&self, f: impl FnOnce(&T) -> U) -> U {
+        let start = std::time::Instant::now();
+        let res = f(&self.inner);
+        self.registry
+            .record_blocking_call(self.inner.kind(), start.elapsed());
+        res
+    }
+
     fn registry(&self) -> &Registry {
         &self.registry
     }
 }
 
+impl<T, Registry> WasiSubscribe for Subscribable<T, Registry>
+where
+    T: WasiSubscribe,
+    Registry: PollableRegistry,
+{
+    fn subscribe(&self) -> Pollable {
+        self.inner.subscribe()
+    }
+
+    fn kind(&self) -> PollableKind {
+        self.inner.kind()
+    }
+}
+
+macro_rules! impl_wrapper_subscribe {
+    ($($ty:ident.$field:ident),+) => {
+        $(
+            impl<Registry: PollableRegistry> WasiSubscribe for $ty<Registry> {
+                fn subscribe(&self) -> Pollable {
+                    self.$field.subscribe()
+                }
+
+                fn kind(&self) -> PollableKind {
+                    self.$field.kind()
+                }
+            }
+        )+
+    }
+}
+impl_wrapper_subscribe!(
+    InputStream.stream,
+    OutputStream.stream,
+    IncomingBody.stream,
+    FutureTrailers.trailers,
+    FutureIncomingResponse.inner
+);
+
 impl<T, Registry: PollableRegistry> std::ops::Deref for Subscribable<T, Registry> {
     type Target = T;
 
@@ -60,28 +116,102 @@ impl<T, Registry: PollableRegistry> std::ops::Deref for Subscribable<T, Registry
 
 pub struct InputStream<Registry: PollableRegistry> {
     stream: Subscribable<types::InputStream, Registry>,
+    // Filled by poll_fill_buf; buf[pos..] has not been consumed yet
+    buf: Vec<u8>,
+    pos: usize,
 }
 
+/// How much poll_fill_buf reads at once.
+const FILL_BUF_SIZE: usize = 16 * 1024;
+
 impl<Registry> InputStream<Registry>
 where
     Registry: PollableRegistry,
 {
     pub fn new(stream: types::InputStream, registry: Registry) -> Self {
         let stream = Subscribable::new(stream, registry);
-        Self { stream }
+        Self {
+            stream,
+            buf: Vec::new(),
+            pos: 0,
+        }
     }
 
+    /// Reads up to `len` bytes, returning Err(WasiStreamClosed) at the end of
+    /// the stream.
     pub fn poll_read(&mut self, cx: &mut Context, len: usize) -> Poll<Result<Vec<u8>, Error>> {
+        if self.pos < self.buf.len() {
+            let end = self.buf.len().min(self.pos + len);
+            let data = self.buf[self.pos..end].to_vec();
+            self.consume(data.len());
+            return Poll::Ready(Ok(data));
+        }
+        self.poll_read_stream(cx, len)
+    }
+
+    /// Returns buffered data, reading more from the stream if the buffer is
+    /// empty. Returns an empty slice at the end of the stream.
+    pub fn poll_fill_buf(&mut self, cx: &mut Context) -> Poll<Result<&[u8], Error>> {
+        if self.pos == self.buf.len() {
+            match self.poll_read_stream(cx, FILL_BUF_SIZE) {
+                Poll::Ready(Ok(data)) => {
+                    self.buf = data;
+                    self.pos = 0;
+                }
+                Poll::Ready(Err(Error::WasiStreamClosed)) => return Poll::Ready(Ok(&[])),
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(&self.buf[self.pos..]))
+    }
+
+    /// Marks `amt` bytes returned by poll_fill_buf as consumed.
+    pub fn consume(&mut self, amt: usize) {
+        self.pos = self.buf.len().min(self.pos + amt);
+    }
+
+    /// Blocks until up to `len` bytes can be read, returning
+    /// Err(WasiStreamClosed) at the end of the stream.
+    pub fn blocking_read(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        if self.pos < self.buf.len() {
+            let end = self.buf.len().min(self.pos + len);
+            let data = self.buf[self.pos..end].to_vec();
+            self.consume(data.len());
+            return Ok(data);
+        }
+        self.stream
+            .blocking(|stream| stream.blocking_read(len.try_into().unwrap()))
+            .map_err(Error::wasi_stream_error)
+    }
+
+    /// Skips up to `len` bytes without reading them into guest memory,
+    /// returning how many were skipped, or Err(WasiStreamClosed) at the end of
+    /// the stream.
+    pub fn poll_skip(&mut self, cx: &mut Context, len: u64) -> Poll<Result<u64, Error>> {
+        if self.pos < self.buf.len() {
+            let skipped = (self.buf.len() - self.pos).min(len.try_into().unwrap_or(usize::MAX));
+            self.consume(skipped);
+            return Poll::Ready(Ok(skipped as u64));
+        }
+        let skipped = self.stream.skip(len).map_err(Error::wasi_stream_error)?;
+        if skipped > 0 {
+            return Poll::Ready(Ok(skipped));
+        }
+        self.stream.register_subscribe(cx);
+        Poll::Pending
+    }
+
+    fn poll_read_stream(&mut self, cx: &mut Context, len: usize) -> Poll<Result<Vec<u8>, Error>> {
         let data = self
             .stream
             .read(len.try_into().unwrap())
             .map_err(Error::wasi_stream_error)?;
-        if data.is_empty() {
-            self.stream.register_subscribe(cx);
-            Poll::Pending
-        } else {
-            Poll::Ready(Ok(data))
+        if !data.is_empty() {
+            return Poll::Ready(Ok(data));
         }
+        self.stream.register_subscribe(cx);
+        Poll::Pending
     }
 
     fn registry(&self) -> &Registry {
@@ -89,6 +219,9 @@ where
     }
 }
 
+/// The most that output-stream.blocking-write-and-flush may be given at once.
+const BLOCKING_WRITE_MAX: usize = 4096;
+
 pub struct OutputStream<Registry: PollableRegistry> {
     stream: Subscribable<types::OutputStream, Registry>,
 }
@@ -105,7 +238,7 @@ where
     pub fn poll_check_write(
         &mut self,
         cx: &mut Context,
-    ) -> Poll<Result<OutputStreamPermit, Error>> {
+    ) -> Poll<Result<OutputStreamPermit<'_>, Error>> {
         let size = self
             .stream
             .check_write()
@@ -121,25 +254,71 @@ where
         }
     }
 
-    pub fn poll_splice(
+    /// Writes as much of `contents` as the stream will accept, returning the
+    /// number of bytes written.
+    pub fn poll_write(&mut self, cx: &mut Context, contents: &[u8]) -> Poll<Result<usize, Error>> {
+        if contents.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let size = self
+            .stream
+            .check_write()
+            .map_err(Error::wasi_stream_error)?;
+        if size > 0 {
+            let permit = OutputStreamPermit {
+                stream: &self.stream.inner,
+                size,
+            };
+            return Poll::Ready(permit.write(contents));
+        }
+        self.stream.register_subscribe(cx);
+        Poll::Pending
+    }
+
+    /// Splices up to `len` bytes from `src` on the host, without copying them
+    /// through guest memory. Returns Err(WasiStreamClosed) at the end of
+    /// `src`. `src` may use a different registry, such as a
+    /// [`CurrentRegistry`](crate::current::CurrentRegistry).
+    pub fn poll_splice<SrcRegistry>(
         &mut self,
         cx: &mut Context,
-        src: &InputStream<Registry>,
+        src: &mut InputStream<SrcRegistry>,
         len: u64,
-    ) -> Poll<Result<u64, Error>> {
+    ) -> Poll<Result<u64, Error>>
+    where
+        SrcRegistry: PollableRegistry,
+    {
         if len == 0 {
             return Poll::Ready(Ok(0));
         }
+        if src.pos < src.buf.len() {
+            // Data already read into src's buffer has to be written out first
+            let end = src
+                .buf
+                .len()
+                .min(src.pos + len.try_into().unwrap_or(usize::MAX));
+            let written = ready!(self.poll_write(cx, &src.buf[src.pos..end]))?;
+            src.consume(written);
+            return Poll::Ready(Ok(written as u64));
+        }
         let size = self
             .stream
             .splice(&src.stream.inner, len)
             .map_err(Error::wasi_stream_error)?;
-        if size == 0 {
+        if size > 0 {
+            return Poll::Ready(Ok(size));
+        }
+        // Wait on whichever side is holding the splice up
+        let writable = self
+            .stream
+            .check_write()
+            .map_err(Error::wasi_stream_error)?;
+        if writable == 0 {
             self.stream.register_subscribe(cx);
-            Poll::Pending
         } else {
-            Poll::Ready(Ok(size))
+            src.stream.register_subscribe(cx);
         }
+        Poll::Pending
     }
 
     pub fn poll_flush(&mut self, cx: &mut Context) -> Poll<Result<(), Error>> {
@@ -147,11 +326,52 @@ where
         self.stream.maybe_subscribe(cx).map(|()| Ok(()))
     }
 
+    /// Blocks until some of `contents` is written and flushed, returning the
+    /// number of bytes written.
+    pub fn blocking_write(&mut self, contents: &[u8]) -> Result<usize, Error> {
+        let len = contents.len().min(BLOCKING_WRITE_MAX);
+        self.stream
+            .blocking(|stream| stream.blocking_write_and_flush(&contents[..len]))
+            .map_err(Error::wasi_stream_error)?;
+        Ok(len)
+    }
+
+    /// Blocks until all written data is flushed.
+    pub fn blocking_flush(&mut self) -> Result<(), Error> {
+        self.stream
+            .blocking(|stream| stream.blocking_flush())
+            .map_err(Error::wasi_stream_error)
+    }
+
     fn registry(&self) -> &Registry {
         self.stream.registry()
     }
 }
 
+/// Returns the component's wasi:cli standard input.
+pub fn stdin<Registry>(registry: Registry) -> InputStream<Registry>
+where
+    Registry: PollableRegistry,
+{
+    InputStream::new(wasi::cli::stdin::get_stdin(), registry)
+}
+
+/// Returns the component's wasi:cli standard output.
+pub fn stdout<Registry>(registry: Registry) -> OutputStream<Registry>
+where
+    Registry: PollableRegistry,
+{
+    OutputStream::new(wasi::cli::stdout::get_stdout(), registry)
+}
+
+/// Returns the component's wasi:cli standard error.
+pub fn stderr<Registry>(registry: Registry) -> OutputStream<Registry>
+where
+    Registry: PollableRegistry,
+{
+    OutputStream::new(wasi::cli::stderr::get_stderr(), registry)
+}
+
 pub struct OutputStreamPermit<'a> {
     stream: &'a types::OutputStream,
     size: u64,
@@ -219,13 +439,8 @@ where
     type Output = Result<Option<FieldEntries>, Error>;
 
     fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match self.trailers.get() {
-            Some(Ok(Ok(Some(fields)))) => Poll::Ready(Ok(Some(fields.into()))),
-            Some(Ok(Ok(None))) => Poll::Ready(Ok(None)),
-            Some(Ok(Err(err))) => Poll::Ready(Err(Error::wasi_error_code(err))),
-            Some(Err(())) => Poll::Ready(Err(Error::WasiInvalidState(
-                "future-trailers.get already consumed",
-            ))),
+        match self.get() {
+            Some(res) => Poll::Ready(res),
             None => {
                 self.trailers.register_subscribe(cx);
                 Poll::Pending
@@ -234,6 +449,31 @@ where
     }
 }
 
+impl<Registry> FutureTrailers<Registry>
+where
+    Registry: PollableRegistry,
+{
+    /// Blocks until the trailers are available.
+    pub fn blocking_get(self) -> Result<Option<FieldEntries>, Error> {
+        self.trailers
+            .blocking(|trailers| trailers.subscribe().block());
+        self.get()
+            .expect("future-trailers not ready after its pollable was")
+    }
+
+    fn get(&self) -> Option<Result<Option<FieldEntries>, Error>> {
+        let res = match self.trailers.get()? {
+            Ok(Ok(Some(fields))) => Ok(Some(fields.into())),
+            Ok(Ok(None)) => Ok(None),
+            Ok(Err(err)) => Err(Error::wasi_error_code(err)),
+            Err(()) => Err(Error::WasiInvalidState(
+                "future-trailers.get already consumed",
+            )),
+        };
+        Some(res)
+    }
+}
+
 #[derive(Debug)]
 pub enum Method {
     Get,
@@ -0,0 +1,315 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    ptr::NonNull,
+    rc::Rc,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+};
+
+use ::wstd::runtime::{AsyncPollable, WaitFor};
+use futures_util::FutureExt;
+use slab::Slab;
+use wasi::io::poll::Pollable;
+
+use crate::{
+    coop::{self, Budget},
+    current,
+    poll::{BlockOnError, PollableKind, PollableRegistry, Stalled, RECENT_REGISTRATIONS},
+};
+
+thread_local! {
+    // wstd's handles are reference counted, so they are kept here rather than
+    // in WstdRegistration to keep registrations (and the bodies holding them)
+    // Send.
+    static REGISTRATIONS: RefCell<Slab<Waiting>> = const { RefCell::new(Slab::new()) };
+    // (key, id) of registrations wstd has found ready; see drain_fired
+    static FIRED: RefCell<Vec<(usize, u64)>> = const { RefCell::new(Vec::new()) };
+    static NEXT_ID: Cell<u64> = const { Cell::new(0) };
+    static RECENT: RefCell<VecDeque<PollableKind>> = const { RefCell::new(VecDeque::new()) };
+}
+
+// Its address identifies WstdRegistry, as there is only one wstd reactor
+static WSTD_ID: u8 = 0;
+
+struct Waiting {
+    // NOTE: order matters; wait must be dropped before pollable. None while
+    // the pollable isn't being waited on.
+    wait: Option<WaitFor>,
+    pollable: AsyncPollable,
+    // Tells apart registrations that reuse a key
+    id: u64,
+}
+
+impl Waiting {
+    /// Waits on the pollable for the context's waker. Returns true if it is
+    /// already ready, in which case it isn't waited on.
+    fn arm(&mut self, cx: &Context, key: usize) -> bool {
+        let wait = self.wait.get_or_insert_with(|| self.pollable.wait_for());
+        let waker = Waker::from(Arc::new(FiredWaker {
+            key,
+            id: self.id,
+            waker: cx.waker().clone(),
+        }));
+        if Pin::new(wait)
+            .poll(&mut Context::from_waker(&waker))
+            .is_pending()
+        {
+            return false;
+        }
+        self.wait = None;
+        true
+    }
+}
+
+/// Notes that a registration's pollable is ready before waking its task.
+struct FiredWaker {
+    key: usize,
+    id: u64,
+    waker: Waker,
+}
+
+impl Wake for FiredWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        FIRED.with_borrow_mut(|fired| fired.push((self.key, self.id)));
+        self.waker.wake_by_ref();
+    }
+}
+
+/// Stops waiting on pollables that wstd has found ready. wstd keeps waking
+/// a waiter for as long as its pollable is ready, and can't be told to stop
+/// while it is waking them, so this is done the next time the registry is
+/// used.
+fn drain_fired() {
+    let fired = FIRED.take();
+    if fired.is_empty() {
+        return;
+    }
+    let waits = REGISTRATIONS.with_borrow_mut(|registrations| {
+        fired
+            .into_iter()
+            .filter_map(|(key, id)| {
+                let waiting = registrations
+                    .get_mut(key)
+                    .filter(|waiting| waiting.id == id)?;
+                waiting.wait.take()
+            })
+            .collect::<Vec<_>>()
+    });
+    // Dropped outside the borrow as this calls back into wstd's reactor
+    drop(waits);
+}
+
+/// Forgets every registration once the reactor they belong to has stopped,
+/// as dropping them would call into it. Leaks a little memory per
+/// registration; the pollables themselves were dropped with the reactor.
+fn forget_registrations() {
+    FIRED.take();
+    for (_, waiting) in REGISTRATIONS.take() {
+        std::mem::forget(waiting);
+    }
+}
+
+/// A PollableRegistry that waits on pollables with wstd's reactor, so that
+/// futures from this crate and from wstd share a single event loop.
+///
+/// [`block_on`](PollableRegistry::block_on) runs `wstd::runtime::block_on`,
+/// so wstd futures can be awaited inside it, e.g. in a handler passed to
+/// `handle_service_call`. Its futures can also be awaited inside a
+/// `wstd::runtime::block_on` started elsewhere, as long as they are dropped
+/// before it returns.
+///
+/// Only wstd can wait on its reactor, so [`poll`](PollableRegistry::poll)
+/// always returns false; driving this registry by hand reports Stalled.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WstdRegistry;
+
+impl PollableRegistry for WstdRegistry {
+    type RegisteredPollable = WstdRegistration;
+
+    fn register_pollable(
+        &self,
+        cx: &mut Context,
+        pollable: Pollable,
+        kind: PollableKind,
+    ) -> Self::RegisteredPollable {
+        drain_fired();
+        // SAFETY: both bindings refer to the same wasi:io/poll pollable
+        // resource, and ownership of the handle is moved out of `pollable`.
+        let pollable = unsafe { wasip2::io::poll::Pollable::from_handle(pollable.take_handle()) };
+        let mut waiting = Waiting {
+            wait: None,
+            pollable: AsyncPollable::new(pollable),
+            id: NEXT_ID.replace(NEXT_ID.get() + 1),
+        };
+        RECENT.with_borrow_mut(|recent| {
+            if recent.len() == RECENT_REGISTRATIONS {
+                recent.pop_front();
+            }
+            recent.push_back(kind);
+        });
+        let id = waiting.id;
+        let (key, ready) = REGISTRATIONS.with_borrow_mut(|registrations| {
+            let entry = registrations.vacant_entry();
+            let key = entry.key();
+            let ready = waiting.arm(cx, key);
+            entry.insert(waiting);
+            (key, ready)
+        });
+        if ready {
+            cx.waker().wake_by_ref();
+        }
+        WstdRegistration { key, id }
+    }
+
+    fn rearm(&self, cx: &mut Context, registered: &mut Self::RegisteredPollable) -> bool {
+        drain_fired();
+        let ready = REGISTRATIONS.with_borrow_mut(|registrations| {
+            let waiting = registrations
+                .get_mut(registered.key)
+                .filter(|waiting| waiting.id == registered.id)?;
+            Some(waiting.arm(cx, registered.key))
+        });
+        match ready {
+            Some(ready) => {
+                if ready {
+                    cx.waker().wake_by_ref();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn poll(&self) -> bool {
+        false
+    }
+
+    fn id(&self) -> Option<usize> {
+        Some(&WSTD_ID as *const u8 as usize)
+    }
+
+    fn name(&self) -> String {
+        "WstdRegistry".into()
+    }
+
+    fn recent_registrations(&self) -> Vec<PollableKind> {
+        RECENT.with_borrow(|recent| recent.iter().copied().collect())
+    }
+
+    /// Runs `fut` to completion as a task on a new wstd runtime, along with
+    /// any other tasks spawned on it.
+    ///
+    /// Returns Err(Stalled) if the runtime runs out of work before `fut`
+    /// completes. `fut` is then leaked rather than dropped, as wstd's own
+    /// futures can't be dropped once its runtime has stopped. Panics if a
+    /// wstd runtime is already running outside of this registry.
+    fn block_on<T>(&self, fut: impl Future<Output = T>) -> Result<T, BlockOnError> {
+        if self.id().is_some_and(current::is_entered) {
+            return Err(BlockOnError::Reentrant(self.name()));
+        }
+        let _guard = current::enter(self);
+
+        let output = Rc::new(Cell::new(None));
+        let root: Box<dyn Future<Output = ()> + '_> = Box::new({
+            let output = output.clone();
+            // Fused so that fut is dropped as soon as it completes, while the
+            // runtime is still running
+            async move { output.set(Some(fut.await)) }.fuse()
+        });
+        let root = Box::into_raw(root);
+        // SAFETY: only the lifetime is changed. The pointer is only
+        // dereferenced through `scope`, which ScopeGuard clears before this
+        // function returns or unwinds, and root is not freed before then.
+        let erased = unsafe {
+            std::mem::transmute::<
+                NonNull<dyn Future<Output = ()> + '_>,
+                NonNull<dyn Future<Output = ()> + 'static>,
+            >(NonNull::new_unchecked(root))
+        };
+        let scope = Rc::new(Scope {
+            root: Cell::new(Some(erased)),
+            rounds: Cell::new(0),
+        });
+        let guard = ScopeGuard(scope.clone());
+
+        // The root task returns straight away, so the runtime only stops once
+        // it runs out of work, rather than panicking if fut stalls
+        let task = ScopedTask(scope.clone());
+        ::wstd::runtime::block_on(async move { ::wstd::runtime::spawn(task).detach() });
+        drop(guard);
+        forget_registrations();
+
+        match output.take() {
+            Some(output) => {
+                // SAFETY: root came from Box::into_raw above, and the task
+                // that polled it can no longer reach it
+                drop(unsafe { Box::from_raw(root) });
+                Ok(output)
+            }
+            None => Err(
+                Stalled::new(self.name(), scope.rounds.get(), self.recent_registrations()).into(),
+            ),
+        }
+    }
+}
+
+/// The future run by [`WstdRegistry::block_on`], which borrows from its
+/// caller, as seen by the 'static task polling it.
+struct Scope {
+    // None once block_on has returned
+    root: Cell<Option<NonNull<dyn Future<Output = ()>>>>,
+    rounds: Cell<usize>,
+}
+
+struct ScopeGuard(Rc<Scope>);
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        self.0.root.set(None);
+    }
+}
+
+struct ScopedTask(Rc<Scope>);
+
+impl Future for ScopedTask {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let scope = &self.0;
+        let Some(root) = scope.root.get() else {
+            return Poll::Ready(());
+        };
+        scope.rounds.set(scope.rounds.get() + 1);
+        drain_fired();
+        // SAFETY: root is still owned and kept in place by block_on, which
+        // clears it before freeing it, and nothing else dereferences it
+        let root = unsafe { Pin::new_unchecked(&mut *root.as_ptr()) };
+        coop::with_budget(Budget::default(), || root.poll(cx))
+    }
+}
+
+/// A pollable registered with a [`WstdRegistry`].
+pub struct WstdRegistration {
+    key: usize,
+    id: u64,
+}
+
+impl Drop for WstdRegistration {
+    fn drop(&mut self) {
+        // Not found if the registration outlived block_on's runtime
+        let waiting =
+            REGISTRATIONS.with_borrow_mut(|registrations| match registrations.get(self.key) {
+                Some(waiting) if waiting.id == self.id => Some(registrations.remove(self.key)),
+                _ => None,
+            });
+        // Dropped outside the borrow as this calls back into wstd's reactor
+        drop(waiting);
+    }
+}